@@ -2,8 +2,30 @@
 --- @field public version string Installed Divina version
 --- @field public Type table<string, number> Package type
 --- @field public Arch table<string, number> Architecture to compile for
+--- @field public os string Operating system Divina runs on, such as `linux`, `macos` or `windows`
+--- @field public host_arch string Architecture Divina runs on, such as `x86_64` or `aarch64`
+--- @field public profile string Name of the build profile selected with `--release` or `--profile <name>`, `debug` by default
+--- @field public env table<string, string> Read-only environment variables, including those loaded from `.env`
+--- @field public workspace_root string Absolute path of the workspace, or of the package outside of one
+--- @field public package_dir string Absolute path of the directory of the `Divina.lua` being evaluated
+--- @field public fs DivinaFs Filesystem access, relative paths are relative to `package_dir`
+--- @field public path DivinaPath Path manipulation
+--- @field public glob fun(pattern: string): string[] Paths which match `pattern`, such as `src/**/*.asm`, relative to `package_dir` if `pattern` is
+--- @field public semver DivinaSemver Semantic version comparison
+--- @field public exec fun(command: string): ExecResult Run `command` with the system shell in `package_dir`, capturing its output
 Divina = {
   version,
+  os,
+  host_arch,
+  profile,
+  env,
+  workspace_root,
+  package_dir,
+  fs,
+  path,
+  glob,
+  semver,
+  exec,
   Type = {
     Bin = 1,
     Lib = 2,
@@ -14,8 +36,23 @@ Divina = {
   },
 }
 
---- @return void
-function test() end
+--- @class DivinaFs
+--- @field public exists fun(path: string): boolean Whether `path` exists
+--- @field public read fun(path: string): string Contents of the file at `path`
+--- @field public list fun(path: string): string[] Names of the entries of the directory at `path`, sorted
+
+--- @class DivinaPath
+--- @field public join fun(...: string): string Join paths with the platform's separator
+--- @field public relative fun(path: string, base: string): string `path`, relative to `base`
+
+--- @class DivinaSemver
+--- @field public compare fun(left: string, right: string): number `-1`, `0` or `1` if `left` is older than, the same as or newer than `right`
+--- @field public satisfies fun(version: string, requirement: string): boolean Whether `version` matches `requirement`, such as `>=0.1.0, <0.2.0`
+
+--- @class ExecResult
+--- @field public stdout string Standard output of the command
+--- @field public stderr string Standard error of the command
+--- @field public exit_code number Exit code of the command, `nil` if it was killed by a signal
 
 --- @class Package
 --- @field public name string
@@ -26,6 +63,19 @@ function test() end
 --- @field public sources string[]
 --- @field public compiler string
 --- @field public visual_studio string
+--- @field public include_dirs string[] Directories passed to the assembler with `-I`, relative to the package
+--- @field public defines table<string, string> Preprocessor defines passed to the assembler with `-D`
+--- @field public profiles table<string, Profile> Build profiles, selected with `--release` or `--profile <name>`
+--- @field public listing boolean Write an assembler listing file (`<source>.lst`) for every source
+--- @field public map boolean Write a linker map file (`<name>.map`)
+--- @field public tests table<string, Test> Tests which `divina test` runs the binary with
+--- @field public unit_tests table<string, UnitTest> Functions which `divina test` calls through a generated C harness, keyed by symbol
+--- @field public benches table<string, Bench> Benchmarks which `divina bench` runs the binary with
+--- @field public hooks Hooks Functions which Divina calls at points of the build, raising an error fails the build
+--- @field public build fun(context: HookContext) Generates sources into `context.generated_dir`, every `.asm` file in it is compiled and it is added to the include paths. A `build.lua` beside `Divina.lua` is used if this is unset, receiving the context as `...`
+--- @field public build_inputs string[] Files which `build` reads, it only reruns once one of them, `Divina.lua` or `build.lua` changes
+--- @field public constants table<string, string|number> Constants written to a generated `constants.inc` in the include paths, as `%define` for NASM and YASM, `.set` for GNU as and `equ` otherwise
+--- @field public structs table<string, StructField[]> Struct layouts written to `constants.inc`, as the offset of every field (`<struct>_<field>`) and the size of the struct (`<struct>_size`)
 Package = {
   name,
   version,
@@ -37,8 +87,69 @@ Package = {
   arch,
   compiler,
   visual_studio,
+  include_dirs,
+  defines,
+  profiles,
+  listing,
+  map,
+  tests,
+  unit_tests,
+  benches,
+  hooks,
+  build,
+  build_inputs,
+  constants,
+  structs,
 }
 
+--- @class Profile
+--- @field public compile_options string[] Extra assembler options, replacing the built-in ones
+--- @field public link_options string[] Extra linker options, replacing the built-in ones
+--- @field public strip boolean Strip symbols from the binary, `true` for `release` by default
+
+--- @class Test
+--- @field public args string[] Arguments passed to the binary
+--- @field public stdin string Standard input passed to the binary
+--- @field public stdout string Expected standard output
+--- @field public stderr string Expected standard error
+--- @field public golden string File which holds the expected standard output, updated by `divina test --bless`
+--- @field public exit_code number Expected exit code, `0` by default
+--- @field public timeout number Seconds after which the binary is killed and the test fails
+--- @field public cpu_limit number Seconds of CPU time after which the binary is killed and the test fails
+--- @field public memory_limit number MiB of address space which the binary may use, not enforced under `--valgrind`
+
+--- @class Bench
+--- @field public args string[] Arguments passed to the binary
+--- @field public stdin string Standard input passed to the binary
+--- @field public iterations number Number of timed runs, `100` by default
+--- @field public warmup number Number of untimed runs before the timed runs, `3` by default
+
+--- @class UnitTest
+--- @field public signature string C prototype of the function, such as `long add_numbers(long, long)`
+--- @field public cases UnitTestCase[] Calls of the function, the harness is linked against every object of the package with its `main` and `_start` renamed (using `objcopy`, on Unix)
+
+--- @class UnitTestCase
+--- @field public args string[] Arguments passed to the function, as C expressions
+--- @field public expected string Value which the function is expected to return, as a C expression
+
+--- @class Hooks
+--- @field public pre_build fun(context: HookContext) Called before the package's sources are compiled
+--- @field public post_compile fun(context: HookContext) Called once every source compiled successfully
+--- @field public post_link fun(context: HookContext) Called once the binary linked successfully
+
+--- @class StructField
+--- @field public name string Name of the field
+--- @field public size number Size of the field in bytes, fields are laid out in order without padding
+
+--- @class HookContext
+--- @field public package string Name of the package
+--- @field public version string Version of the package
+--- @field public profile string Name of the selected build profile
+--- @field public directory string Directory of the package, relative to the workspace
+--- @field public out_dir string Directory which objects are compiled into, relative to the workspace
+--- @field public generated_dir string Directory which `Package.build` generates sources into, relative to the workspace
+--- @field public artifact string Binary or library which is linked, relative to the workspace
+
 --- @class Workspace
 --- @field public members string[]
 Workspace = {
@@ -0,0 +1,127 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use divina_compile::{BenchCase, Statistics};
+
+/// `seconds` in the largest unit which keeps it above one
+fn format_duration(seconds: f64) -> String {
+  if seconds >= 1.0 {
+    format!("{:.3} s", seconds)
+  } else if seconds >= 1e-3 {
+    format!("{:.3} ms", seconds * 1e3)
+  } else if seconds >= 1e-6 {
+    format!("{:.3} µs", seconds * 1e6)
+  } else {
+    format!("{:.3} ns", seconds * 1e9)
+  }
+}
+
+/// Run every benchmark, saving their statistics as the baseline `save` and
+/// comparing them against the baseline `baseline`
+///
+/// Benchmarks whose median is more than `threshold` percent slower than the
+/// baseline's are regressions. Exits if any benchmark regressed or failed.
+pub fn run(cases: &[BenchCase], save: &str, baseline: Option<&str>, threshold: f64) {
+  let previous = baseline.map(|name| {
+    (
+      name,
+      divina_compile::load_baseline(name).unwrap_or_else(|| {
+        divina_util::exit_with!(
+          1,
+          "!! baseline '{}' has not been saved, save it with `--save-baseline {}`",
+          name,
+          name
+        );
+      }),
+    )
+  });
+  // Benches which were filtered out keep their previous results
+  let mut statistics = divina_compile::load_baseline(save).unwrap_or_default();
+  let mut failed = 0;
+  let mut regressed = 0;
+
+  divina_util::output::status(&format!(
+    ":: running {} bench{}",
+    cases.len(),
+    if cases.len() == 1 { "" } else { "es" }
+  ));
+
+  for case in cases {
+    divina_util::output::status(&format!(
+      ":: {} @@ bench '{}' ... {} iteration{}",
+      case.package,
+      case.name,
+      case.iterations,
+      if case.iterations == 1 { "" } else { "s" }
+    ));
+
+    let current: Statistics = match case.run() {
+      Ok(current) => current,
+      Err(error) => {
+        divina_util::output::status(&format!("   FAILED, {}", error));
+        failed += 1;
+
+        continue;
+      }
+    };
+
+    divina_util::output::status(&format!(
+      "   mean {} ± {}, median {}, min {}, max {}, {} outlier{}",
+      format_duration(current.mean),
+      format_duration(current.stddev),
+      format_duration(current.median),
+      format_duration(current.min),
+      format_duration(current.max),
+      current.outliers,
+      if current.outliers == 1 { "" } else { "s" }
+    ));
+
+    if let Some((name, previous)) = &previous {
+      if let Some(previous) = previous.get(&case.key()) {
+        let change = current.change(previous) * 100.0;
+
+        divina_util::output::status(&format!(
+          "   median {:+.2}% against baseline '{}', {}",
+          change,
+          name,
+          if change > threshold {
+            regressed += 1;
+
+            "regressed"
+          } else if change < -threshold {
+            "improved"
+          } else {
+            "within threshold"
+          }
+        ));
+      } else {
+        divina_util::output::status(&format!("   not in baseline '{}'", name));
+      }
+    }
+
+    statistics.insert(case.key(), current);
+  }
+
+  divina_compile::save_baseline(save, &statistics);
+  divina_util::output::status(&format!(":: saved baseline '{}'", save));
+
+  if failed > 0 {
+    divina_util::exit_with!(
+      1,
+      "!! {} bench{} failed",
+      failed,
+      if failed == 1 { "" } else { "es" }
+    );
+  }
+
+  if regressed > 0 {
+    divina_util::exit_with!(
+      1,
+      "!! {} bench{} regressed by more than {}% against baseline '{}'",
+      regressed,
+      if regressed == 1 { "" } else { "es" },
+      threshold,
+      baseline.unwrap_or_default()
+    );
+  }
+}
@@ -3,17 +3,81 @@
 
 use std::path::Path;
 
-use structopt::clap::{App, AppSettings, Arg, SubCommand};
+use structopt::clap::{App, AppSettings, Arg, ArgMatches, Shell, SubCommand};
+
+/// Divina's own subcommands, by their path below `divina`, and what they do
+///
+/// `divina --list` and `divina man` are generated from these, as clap 2 only
+/// exposes the subcommands of an `App` through `#[doc(hidden)]` fields.
+pub const SUBCOMMANDS: &[(&str, &str)] = &[
+  ("init", ""),
+  ("build", "Build your project"),
+  ("compdb", "Write a compile_commands.json for editor tooling"),
+  (
+    "generate",
+    "Export your build as a Ninja or Make build file",
+  ),
+  ("run", "Build your project and run its binary"),
+  (
+    "test",
+    "Build your project and run the tests in `Package.tests` and `Package.unit_tests`",
+  ),
+  (
+    "bench",
+    "Build your project with the release profile and run `Package.benches`",
+  ),
+  ("debug", "Build your project and debug its binary with GDB"),
+  (
+    "clean",
+    "Cleanup Divina's non-essential temporary files and directories",
+  ),
+  ("config", ""),
+  ("config show", "Print your configuration"),
+  (
+    "config validate",
+    "Check if your configuration will compile",
+  ),
+  ("config members", "Print the name of every workspace member"),
+  ("config compiler", "Access the Divina compiler wrapper"),
+  (
+    "config compiler show",
+    "Print Divina's compiler configuration",
+  ),
+  ("completions", "Print a shell completion script"),
+  ("man", "Print Divina's manual page as roff"),
+  (
+    "metadata",
+    "Print a JSON description of your workspace for tooling",
+  ),
+];
+
+/// What the subcommand at `path` does, as listed in `SUBCOMMANDS`
+fn about(path: &str) -> &'static str {
+  SUBCOMMANDS
+    .iter()
+    .find(|(name, _)| *name == path)
+    .map_or_else(
+      || {
+        panic!(
+          "!! subcommand `{}` is not listed in `SUBCOMMANDS`, this *shouldn't* be possible",
+          path
+        )
+      },
+      |(_, about)| *about,
+    )
+}
 
 /// Create CLI
-fn cli() -> App<'static, 'static> {
+#[allow(clippy::too_many_lines)]
+pub fn cli() -> App<'static, 'static> {
   App::new(env!("CARGO_PKG_NAME"))
     .about(env!("CARGO_PKG_DESCRIPTION"))
     .version(env!("CARGO_PKG_VERSION"))
     .author(env!("CARGO_PKG_AUTHORS"))
-    .setting(AppSettings::SubcommandRequiredElseHelp)
+    // Unknown subcommands are dispatched to plugins
+    .setting(AppSettings::AllowExternalSubcommands)
     .subcommands(vec![
-      SubCommand::with_name("init").about("").args(&[
+      SubCommand::with_name("init").about(about("init")).args(&[
         Arg::with_name("type")
           .long("type")
           .takes_value(true)
@@ -21,34 +85,436 @@ fn cli() -> App<'static, 'static> {
         Arg::with_name("git").long("git").takes_value(true),
         Arg::with_name("path").index(1).takes_value(true),
       ]),
-      SubCommand::with_name("build").about("Build your project"),
+      SubCommand::with_name("build")
+        .about(about("build"))
+        .args(&profile_args())
+        .args(&[
+          Arg::with_name("listing")
+            .long("listing")
+            .help("Write an assembler listing file for every source"),
+          Arg::with_name("map")
+            .long("map")
+            .help("Write a linker map file for every package"),
+          Arg::with_name("message-format")
+            .long("message-format")
+            .takes_value(true)
+            .possible_values(&["human", "json"])
+            .default_value("human")
+            .help("How build progress is reported"),
+          Arg::with_name("dry-run")
+            .long("dry-run")
+            .help("Print every assembler and linker command without running them"),
+          Arg::with_name("explain")
+            .long("explain")
+            .help("Print why each command would run, implies `--dry-run`"),
+        ]),
+      SubCommand::with_name("compdb")
+        .about(about("compdb"))
+        .args(&profile_args()),
+      SubCommand::with_name("generate")
+        .about(about("generate"))
+        .args(&profile_args())
+        .args(&[
+          Arg::with_name("generator")
+            .index(1)
+            .required(true)
+            .possible_values(&["ninja", "make"]),
+          Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .takes_value(true)
+            .value_name("path")
+            .help("Where to write the build file, 'build.ninja' or 'Makefile' by default"),
+        ]),
+      SubCommand::with_name("run")
+        .about(about("run"))
+        .args(&profile_args())
+        .args(&[
+          package_arg(),
+          valgrind_arg(),
+          Arg::with_name("args")
+            .multiple(true)
+            .last(true)
+            .help("Arguments passed to the binary"),
+        ]),
+      SubCommand::with_name("test")
+        .about(about("test"))
+        .args(&profile_args())
+        .args(&[
+          package_arg(),
+          Arg::with_name("bless")
+            .long("bless")
+            .help("Update golden files with the output of each test"),
+          Arg::with_name("filter")
+            .index(1)
+            .help("Only run tests whose names contain this"),
+          Arg::with_name("format")
+            .long("format")
+            .takes_value(true)
+            .possible_values(&["human", "junit", "tap"])
+            .default_value("human")
+            .help("How test results are reported"),
+          Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .takes_value(true)
+            .value_name("path")
+            .help("Write the JUnit or TAP report to a file rather than to standard output"),
+          valgrind_arg(),
+          Arg::with_name("cpu-limit")
+            .long("cpu-limit")
+            .takes_value(true)
+            .value_name("seconds")
+            .help("CPU time limit of every test which does not set its own `cpu_limit`"),
+          Arg::with_name("memory-limit")
+            .long("memory-limit")
+            .takes_value(true)
+            .value_name("MiB")
+            .help("Memory limit of every test which does not set its own `memory_limit`"),
+        ]),
+      SubCommand::with_name("bench")
+        .about(about("bench"))
+        .args(&profile_args())
+        .args(&[
+          package_arg(),
+          Arg::with_name("filter")
+            .index(1)
+            .help("Only run benches whose names contain this"),
+          Arg::with_name("save-baseline")
+            .long("save-baseline")
+            .takes_value(true)
+            .value_name("name")
+            .default_value("latest")
+            .help("Save the results as this baseline"),
+          Arg::with_name("baseline")
+            .long("baseline")
+            .takes_value(true)
+            .value_name("name")
+            .help("Compare the results against this previously saved baseline"),
+          Arg::with_name("threshold")
+            .long("threshold")
+            .takes_value(true)
+            .value_name("percent")
+            .default_value("5")
+            .help("How much slower than the baseline a bench may be before it has regressed"),
+        ]),
+      SubCommand::with_name("debug")
+        .about(about("debug"))
+        .args(&profile_args())
+        .args(&[
+          package_arg(),
+          Arg::with_name("args")
+            .multiple(true)
+            .last(true)
+            .help("Arguments passed to the binary"),
+        ]),
       SubCommand::with_name("clean")
-        .about("Cleanup Divina's non-essential temporary files and directories"),
+        .about(about("clean")),
       SubCommand::with_name("config")
-        .about("")
+        .about(about("config"))
         .setting(AppSettings::SubcommandRequiredElseHelp)
         .subcommands(vec![
-          SubCommand::with_name("show").about("Print your configuration"),
-          SubCommand::with_name("validate").about("Check if your configuration will compile"),
+          SubCommand::with_name("show").about(about("config show")),
+          SubCommand::with_name("validate").about(about("config validate")),
+          SubCommand::with_name("members").about(about("config members")),
           SubCommand::with_name("compiler")
-            .about("Access the Divina compiler wrapper")
+            .about(about("config compiler"))
             .setting(AppSettings::SubcommandRequiredElseHelp)
             .subcommands(vec![
-              SubCommand::with_name("show").about("Print Divina's compiler configuration")
+              SubCommand::with_name("show").about(about("config compiler show"))
             ]),
         ]),
+      SubCommand::with_name("completions")
+        .about(about("completions"))
+        .arg(
+          Arg::with_name("shell")
+            .index(1)
+            .required(true)
+            .possible_values(&Shell::variants()),
+        ),
+      SubCommand::with_name("man").about(about("man")),
+      SubCommand::with_name("metadata")
+        .about(about("metadata"))
+        .args(&profile_args()),
     ])
     .args(&[
-      Arg::with_name("debug").short("d").long("debug"),
-      Arg::with_name("trace").short("t").long("trace"),
+      Arg::with_name("list")
+        .long("list")
+        .help("List every subcommand, including plugins"),
+      Arg::with_name("debug")
+        .short("d")
+        .long("debug")
+        .global(true)
+        .help("Log what Divina is doing to standard error"),
+      Arg::with_name("trace")
+        .short("t")
+        .long("trace")
+        .global(true)
+        .help("Log everything Divina is doing to standard error, implies `--debug`"),
+      Arg::with_name("quiet")
+        .short("q")
+        .long("quiet")
+        .global(true)
+        .conflicts_with("verbose")
+        .help("Only print errors and warnings"),
+      Arg::with_name("verbose")
+        .short("v")
+        .long("verbose")
+        .global(true)
+        .help("Also print every command which Divina runs"),
+      Arg::with_name("color")
+        .long("color")
+        .takes_value(true)
+        .global(true)
+        .possible_values(&["auto", "always", "never"])
+        .help("Whether to color output, `auto` honours `NO_COLOR`"),
     ])
 }
 
+/// Select from the global arguments, which apply ahead of `execute`, such as
+/// to `Divina.lua`'s evaluation
+fn global_arguments<T>(matches: &ArgMatches<'_>, select: impl Fn(&ArgMatches<'_>) -> T) -> T {
+  // Global arguments are propagated to the deepest subcommand
+  let mut deepest = matches;
+
+  while let (_, Some(subcommand_matches)) = deepest.subcommand() {
+    deepest = subcommand_matches;
+  }
+
+  select(deepest)
+}
+
+/// Whether the selected subcommand needs `Divina.lua`, those which don't
+/// can be run outside of a project
+#[must_use]
+pub fn requires_config(matches: &ArgMatches<'_>) -> bool {
+  match matches.subcommand_name() {
+    None | Some("completions" | "man") => false,
+    // Plugins may be run anywhere, but are passed the workspace's metadata
+    // within one
+    Some(name) if !is_builtin(name) => Path::new("Divina.lua").exists(),
+    Some(_) => true,
+  }
+}
+
+/// `SUBCOMMANDS` which are run as `divina <name>`, rather than nested within
+/// another subcommand
+fn top_level_subcommands() -> impl Iterator<Item = &'static (&'static str, &'static str)> {
+  SUBCOMMANDS.iter().filter(|(path, _)| !path.contains(' '))
+}
+
+/// Whether `name` is one of Divina's own subcommands rather than a plugin's
+fn is_builtin(name: &str) -> bool { top_level_subcommands().any(|(path, _)| *path == name) }
+
+/// Print every subcommand along with what it does, followed by every plugin
+/// on `PATH`
+fn list() {
+  let plugins = crate::plugin::discover();
+  let width = top_level_subcommands()
+    .map(|(name, _)| *name)
+    .chain(plugins.keys().map(String::as_str))
+    .map(str::len)
+    .max()
+    .unwrap_or_default();
+
+  divina_util::output::print(":: installed subcommands");
+
+  for (name, about) in top_level_subcommands() {
+    println!("   {:width$}  {}", name, about, width = width);
+  }
+
+  if !plugins.is_empty() {
+    divina_util::output::print(":: plugins");
+
+    for (name, path) in plugins {
+      println!("   {:width$}  {}", name, path.display(), width = width);
+    }
+  }
+}
+
+/// Log specification selected by `--debug` or `--trace`
+#[must_use]
+pub fn log_specification(matches: &ArgMatches<'_>) -> Option<&'static str> {
+  global_arguments(matches, |matches| {
+    if matches.is_present("trace") {
+      Some("trace")
+    } else if matches.is_present("debug") {
+      Some("debug")
+    } else {
+      None
+    }
+  })
+}
+
+/// Build profile selected by `profile_args`, both Divina's compiler and
+/// `Divina.lua`'s `Divina.profile` are given this
+///
+/// `divina bench` selects the release profile unless told otherwise.
+#[must_use]
+pub fn profile(matches: &ArgMatches<'_>) -> String {
+  let (name, subcommand_matches) = matches.subcommand();
+  let selected = subcommand_matches.and_then(|subcommand_matches| {
+    if subcommand_matches.is_present("release") {
+      Some("release")
+    } else {
+      subcommand_matches.value_of("profile")
+    }
+  });
+
+  selected
+    .unwrap_or(if name == "bench" {
+      "release"
+    } else {
+      divina_compile::DEFAULT_PROFILE
+    })
+    .to_string()
+}
+
+/// Apply the output level selected by `--quiet` or `--verbose` and the color
+/// choice selected by `--color`
+pub fn configure_output(matches: &ArgMatches<'_>) {
+  use divina_util::output::{self, ColorChoice, Level};
+
+  global_arguments(matches, |matches| {
+    output::set_level(if matches.is_present("quiet") {
+      Level::Quiet
+    } else if matches.is_present("verbose") {
+      Level::Verbose
+    } else {
+      Level::Normal
+    });
+    output::set_color_choice(
+      matches
+        .value_of("color")
+        .and_then(ColorChoice::from_name)
+        .unwrap_or(ColorChoice::Auto),
+    );
+  });
+}
+
+/// Arguments which select a build profile
+fn profile_args() -> Vec<Arg<'static, 'static>> {
+  vec![
+    Arg::with_name("release")
+      .long("release")
+      .help("Build with the release profile")
+      .conflicts_with("profile"),
+    Arg::with_name("profile")
+      .long("profile")
+      .takes_value(true)
+      .value_name("name")
+      .help("Build with the given profile"),
+  ]
+}
+
+/// Argument which selects a workspace member
+fn package_arg() -> Arg<'static, 'static> {
+  Arg::with_name("package")
+    .short("p")
+    .long("package")
+    .takes_value(true)
+    .value_name("member")
+    .help("Name of the workspace member to use")
+}
+
+/// Argument which runs binaries under Valgrind's memcheck
+fn valgrind_arg() -> Arg<'static, 'static> {
+  Arg::with_name("valgrind")
+    .long("valgrind")
+    .help("Run under Valgrind's memcheck, failing on any error which it reports")
+}
+
+/// Parse the value of a limit argument
+fn limit(matches: &ArgMatches<'_>, name: &str) -> Option<u64> {
+  matches.value_of(name).map(|limit| {
+    limit.parse().unwrap_or_else(|_| {
+      divina_util::exit_with!(1, "!! `--{}` must be a whole number, not '{}'", name, limit);
+    })
+  })
+}
+
+/// Find the binary of the package selected by `package_arg`, exiting if it is
+/// a `Lib` package, which has no binary to run or debug
+fn select_artifact(divina: &crate::Divina, matches: &ArgMatches<'_>) -> divina_compile::Artifact {
+  let mut artifacts = divina.compiler.artifacts();
+  let artifact = if let Some(package) = matches.value_of("package") {
+    artifacts
+      .into_iter()
+      .find(|artifact| artifact.package == package)
+      .unwrap_or_else(|| {
+        divina_util::exit_with!(1, "!! no workspace member is named '{}'", package);
+      })
+  } else {
+    // Only binaries can be run, so a lone `Bin` member is selected even
+    // alongside `Lib` members
+    let mut binaries = artifacts
+      .iter()
+      .filter(|artifact| artifact.package_type == divina_config::PackageType::Bin)
+      .cloned()
+      .collect::<Vec<_>>();
+
+    match (binaries.len(), artifacts.len()) {
+      (1, _) => binaries.remove(0),
+      // A lone `Lib` package is selected, so that it is explained below why
+      // it can't be run
+      (0, 1) => artifacts.remove(0),
+      (0, _) => {
+        divina_util::exit_with!(1, "!! this workspace has no `Bin` members to run");
+      }
+      _ => {
+        divina_util::exit_with!(
+          1,
+          "!! this workspace has multiple `Bin` members, select one with `--package` from: {}",
+          binaries
+            .iter()
+            .map(|artifact| artifact.package.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+        );
+      }
+    }
+  };
+
+  if artifact.package_type == divina_config::PackageType::Lib {
+    divina_util::exit_with!(
+      1,
+      "!! could not run '{}', it is a `Lib` package",
+      artifact.package
+    );
+  }
+
+  artifact
+}
+
+/// Exit code which mirrors `status`, binaries killed by a signal exit with
+/// `128` plus the signal's number like they would in a shell
+pub fn exit_code(status: std::process::ExitStatus) -> i32 {
+  #[cfg(unix)]
+  {
+    use std::os::unix::process::ExitStatusExt;
+
+    if let Some(signal) = status.signal() {
+      return 128 + signal;
+    }
+  }
+
+  status.code().unwrap_or(1)
+}
+
 /// Execute CLI
-pub fn execute(divina: &mut crate::Divina) {
-  let matches = cli().get_matches();
+#[allow(clippy::too_many_lines)]
+pub fn execute(divina: &mut crate::Divina, matches: &ArgMatches<'_>) {
+  divina.compiler.set_profile(&profile(matches));
 
   match matches.subcommand() {
+    ("", None) =>
+      if matches.is_present("list") {
+        list();
+      } else {
+        let _ = cli().print_help();
+
+        divina_util::exit_with!(1);
+      },
     ("init", Some(init_matches)) => {
       let repository = init_matches
         .value_of("git")
@@ -66,25 +532,255 @@ pub fn execute(divina: &mut crate::Divina) {
       divina_git::clone(repository, &format!("./{}", path))
         .expect("!! could not clone init repository, perhaps the repository is invalid ?");
     }
-    ("build", Some(_build_matches)) => {
+    ("build", Some(build_matches)) => {
+      if build_matches.is_present("listing") {
+        divina.compiler.enable_listing();
+      }
+      if build_matches.is_present("map") {
+        divina.compiler.enable_map();
+      }
+      if let Some(message_format) = build_matches
+        .value_of("message-format")
+        .and_then(divina_compile::MessageFormat::from_name)
+      {
+        divina.compiler.set_message_format(message_format);
+      }
+
+      divina.compiler.find_sources(divina.expose_config());
+
+      if build_matches.is_present("dry-run") || build_matches.is_present("explain") {
+        divina.compiler.dry_run(build_matches.is_present("explain"));
+      } else {
+        divina.compiler.compile();
+        // Written once `Package.build` has run, so that it lists what it
+        // generated, and never on a dry run, which executes nothing
+        divina.compiler.write_compile_commands();
+        divina.compiler.link();
+        divina.compiler.summarize();
+      }
+    }
+    ("compdb", Some(_compdb_matches)) => {
+      divina
+        .compiler
+        .find_sources(divina.expose_config())
+        .write_compile_commands();
+      divina_util::output::status(":: wrote 'compile_commands.json'");
+    }
+    ("generate", Some(generate_matches)) => {
+      let generator = generate_matches
+        .value_of("generator")
+        .and_then(divina_compile::Generator::from_name)
+        .expect("!! could not access generator, this *shouldn't* be possible");
+      let output = generate_matches
+        .value_of("output")
+        .unwrap_or_else(|| generator.file_name());
+
+      // Never clobber a hand-written build file
+      if let Ok(existing) = std::fs::read_to_string(output) {
+        if !existing.starts_with(divina_compile::GENERATED_HEADER) {
+          divina_util::exit_with!(
+            1,
+            "!! could not write '{}', it exists and was not generated by Divina",
+            output
+          );
+        }
+      }
+
+      divina.compiler.find_sources(divina.expose_config());
+      std::fs::write(output, divina.compiler.generate(generator))
+        .unwrap_or_else(|_| panic!("!! could not write '{}', check permissions", output));
+      divina_util::output::status(&format!(":: wrote '{}'", output));
+    }
+    ("run", Some(run_matches)) => {
+      divina
+        .compiler
+        .find_sources(divina.expose_config())
+        .compile()
+        .link();
+      divina.compiler.summarize();
+
+      let artifact = select_artifact(divina, run_matches);
+
+      divina_util::output::status(&format!(":: running '{}'", artifact.path));
+
+      let mut invocation = divina_compile::Invocation {
+        program:   artifact.path.clone(),
+        arguments: run_matches
+          .values_of("args")
+          .map_or_else(Vec::new, |args| args.map(ToString::to_string).collect()),
+      };
+      let memcheck = if run_matches.is_present("valgrind") {
+        let memcheck = divina_compile::Memcheck::new(&artifact.path);
+
+        let _ = std::fs::remove_file(&memcheck.log);
+        invocation = memcheck.wrap(invocation);
+
+        Some(memcheck)
+      } else {
+        None
+      };
+
+      divina_util::output::verbose(&format!("   `{}`", invocation.command_line()));
+
+      let status = invocation.status().unwrap_or_else(|_| {
+        divina_util::exit_with!(1, "!! could not run '{}'", invocation.program);
+      });
+
+      if let Some(memcheck) = memcheck {
+        if let Some(report) = memcheck.errors() {
+          eprint!("{}", report);
+          divina_util::output::error(&format!(
+            "!! memcheck reported errors, see '{}'",
+            memcheck.log
+          ));
+
+          // Keep the binary's own failure, if it failed
+          std::process::exit(match exit_code(status) {
+            0 => 1,
+            code => code,
+          });
+        }
+      }
+
+      std::process::exit(exit_code(status));
+    }
+    ("test", Some(test_matches)) => {
+      let format = test_matches
+        .value_of("format")
+        .and_then(crate::testing::Format::from_name)
+        .expect("!! could not access test format, this *shouldn't* be possible");
+
+      if format == crate::testing::Format::Human && test_matches.is_present("output") {
+        divina_util::exit_with!(
+          1,
+          "!! `--output` requires `--format junit` or `--format tap`"
+        );
+      }
+
+      // Nothing but the report may be printed to standard output, or TAP and
+      // JUnit consumers would reject it
+      if format != crate::testing::Format::Human && !test_matches.is_present("output") {
+        divina_util::output::set_status_stream(divina_util::output::Stream::Stderr);
+      }
+
+      divina
+        .compiler
+        .find_sources(divina.expose_config())
+        .compile()
+        .link();
+      divina.compiler.summarize();
+
+      let mut cases = divina.compiler.test_cases(
+        test_matches.value_of("package"),
+        test_matches.value_of("filter"),
+      );
+
+      cases.extend(divina.compiler.unit_test_cases(
+        test_matches.value_of("package"),
+        test_matches.value_of("filter"),
+      ));
+
+      for case in &mut cases {
+        case.memcheck = test_matches.is_present("valgrind");
+        case.cpu_limit = case.cpu_limit.or_else(|| limit(test_matches, "cpu-limit"));
+        case.memory_limit = case
+          .memory_limit
+          .or_else(|| limit(test_matches, "memory-limit"));
+      }
+
+      crate::testing::run(
+        &cases,
+        test_matches.is_present("bless"),
+        format,
+        test_matches.value_of("output"),
+      );
+    }
+    ("bench", Some(bench_matches)) => {
+      let threshold = bench_matches
+        .value_of("threshold")
+        .and_then(|threshold| threshold.parse::<f64>().ok())
+        .unwrap_or_else(|| {
+          divina_util::exit_with!(1, "!! `--threshold` must be a number");
+        });
+
       divina
         .compiler
         .find_sources(divina.expose_config())
         .compile()
         .link();
+      divina.compiler.summarize();
+      crate::bench::run(
+        &divina.compiler.bench_cases(
+          bench_matches.value_of("package"),
+          bench_matches.value_of("filter"),
+        ),
+        bench_matches
+          .value_of("save-baseline")
+          .expect("!! could not access baseline name, this *shouldn't* be possible"),
+        bench_matches.value_of("baseline"),
+        threshold,
+      );
+    }
+    ("debug", Some(debug_matches)) => {
+      divina
+        .compiler
+        .find_sources(divina.expose_config())
+        .compile()
+        .link();
+      divina.compiler.summarize();
+
+      let artifact = select_artifact(divina, debug_matches);
+
+      std::process::exit(crate::debug::gdb(
+        &artifact.path,
+        &debug_matches
+          .values_of("args")
+          .map_or_else(Vec::new, Iterator::collect),
+      ));
     }
     ("clean", Some(_clean_matches)) =>
       if Path::new("out/").exists() {
-        println!(":: removing directory 'out/'");
+        divina_util::output::status(":: removing directory 'out/'");
         std::fs::remove_dir_all("out/")
           .expect("!! could not remove directory 'out/', check permissions");
       } else {
-        println!(":: directory 'out/' does not exist");
+        divina_util::output::status(":: directory 'out/' does not exist");
       },
+    ("completions", Some(completions_matches)) =>
+      print!(
+        "{}",
+        crate::completions::script(
+          completions_matches
+            .value_of("shell")
+            .and_then(|shell| shell.parse().ok())
+            .expect("!! could not access shell, this *shouldn't* be possible")
+        )
+      ),
+    ("metadata", Some(_metadata_matches)) => {
+      println!(
+        "{}",
+        divina
+          .compiler
+          .find_sources(divina.expose_config())
+          .metadata()
+      );
+    }
+    ("man", Some(_man_matches)) => print!("{}", crate::man::page()),
     ("config", Some(config_matches)) =>
       match config_matches.subcommand() {
         ("show", _) => divina.print_config(),
-        ("validate", _) => println!(":: no issues found"),
+        ("validate", _) => divina_util::output::status(":: no issues found"),
+        ("members", _) => {
+          let config = divina.expose_config();
+
+          if config.config_type == divina_config::ConfigType::Workspace {
+            for member in config.members.unwrap_or_default() {
+              println!("{}", member.name.unwrap_or_default());
+            }
+          } else {
+            println!("{}", config.name.unwrap_or_default());
+          }
+        }
         ("compiler", Some(config_compiler_matches)) =>
           match config_compiler_matches.subcommand() {
             ("show", _) => {
@@ -97,6 +793,40 @@ pub fn execute(divina: &mut crate::Divina) {
           },
         _ => unreachable!(),
       },
+    (plugin, Some(plugin_matches)) =>
+      crate::plugin::run(
+        divina,
+        plugin,
+        &plugin_matches
+          .values_of("")
+          .map_or_else(Vec::new, Iterator::collect),
+      ),
     _ => unreachable!(),
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use structopt::clap::ErrorKind;
+
+  #[test]
+  fn every_listed_subcommand_exists() {
+    for (path, _) in super::SUBCOMMANDS {
+      let arguments = std::iter::once("divina")
+        .chain(path.split_whitespace())
+        .chain(std::iter::once("--help"));
+
+      // Unknown subcommands would be parsed as a plugin's rather than showing
+      // help
+      assert_eq!(
+        super::cli()
+          .get_matches_from_safe(arguments)
+          .map_err(|error| error.kind)
+          .err(),
+        Some(ErrorKind::HelpDisplayed),
+        "`{}` is not a subcommand",
+        path
+      );
+    }
+  }
+}
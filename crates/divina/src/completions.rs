@@ -0,0 +1,76 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use structopt::clap::Shell;
+
+/// Command which lists the workspace members of the current directory, if it
+/// has a `Divina.lua`
+const MEMBERS: &str = "test -f Divina.lua && divina -q config members 2>/dev/null";
+
+/// Completion script for `shell`, generated from the CLI
+///
+/// clap only completes `--package` with file names, so Bash, Zsh and Fish
+/// scripts are patched to complete it with workspace members instead. Elvish
+/// scripts don't complete values at all and are left as is.
+pub fn script(shell: Shell) -> String {
+  let mut script = Vec::new();
+
+  crate::cli::cli().gen_completions_to(env!("CARGO_PKG_NAME"), shell, &mut script);
+
+  let script = String::from_utf8(script)
+    .expect("!! could not generate completion script, this *shouldn't* be possible");
+
+  match shell {
+    Shell::Bash =>
+      ["--package)", "-p)"].iter().fold(script, |script, option| {
+        script.replace(
+          &format!(
+            "{}\n                    COMPREPLY=($(compgen -f \"${{cur}}\"))",
+            option
+          ),
+          &format!(
+            "{}\n                    COMPREPLY=($(compgen -W \"$({})\" -- \"${{cur}}\"))",
+            option, MEMBERS
+          ),
+        )
+      }),
+    Shell::Zsh => {
+      let script = script
+        .replace(
+          "'-p+[Name of the workspace member to use]'",
+          "'-p+[Name of the workspace member to use]:member:_divina_members'",
+        )
+        .replace(
+          "'--package=[Name of the workspace member to use]'",
+          "'--package=[Name of the workspace member to use]:member:_divina_members'",
+        );
+      let function = format!(
+        "(( $+functions[_divina_members] )) ||\n_divina_members() {{\n    local members\n    \
+         members=(${{(f)\"$({})\"}})\n    _describe -t members 'workspace members' members \
+         \"$@\"\n}}\n\n",
+        MEMBERS
+      );
+
+      match script.rfind("_divina \"$@\"") {
+        Some(position) => format!("{}{}{}", &script[..position], function, &script[position..]),
+        None => script,
+      }
+    }
+    Shell::Fish =>
+      script
+        .lines()
+        .map(|line| {
+          if line.contains(" -l package ") {
+            format!(
+              "{} -r -f -a \"({})\"\n",
+              line,
+              MEMBERS.replace("&&", "; and")
+            )
+          } else {
+            format!("{}\n", line)
+          }
+        })
+        .collect(),
+    _ => script,
+  }
+}
@@ -0,0 +1,70 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::process::Command;
+
+/// Read the entry point address from the header of an ELF binary
+fn entry_point(binary: &str) -> Option<u64> {
+  let header = std::fs::read(binary).ok()?;
+
+  if header.get(0..4)? != b"\x7fELF" {
+    return None;
+  }
+
+  // `e_entry` is a 32-bit address in ELF32 (class 1) and a 64-bit address in
+  // ELF64 (class 2)
+  match header.get(4)? {
+    1 =>
+      Some(u64::from(u32::from_le_bytes(
+        header.get(24..28)?.try_into().ok()?,
+      ))),
+    2 => Some(u64::from_le_bytes(header.get(24..32)?.try_into().ok()?)),
+    _ => None,
+  }
+}
+
+/// Launch GDB on `binary` with a generated init script which sets a
+/// breakpoint on its entry point
+///
+/// Returns GDB's exit code.
+pub fn gdb(binary: &str, arguments: &[&str]) -> i32 {
+  let script_path = format!("{}.gdb", binary);
+  let breakpoint = entry_point(binary).map_or_else(
+    || {
+      divina_util::output::warning(&format!(
+        ":: could not read the entry point of '{}', no breakpoint will be set",
+        binary
+      ));
+
+      String::new()
+    },
+    |entry_point| format!("break *{:#x}\n", entry_point),
+  );
+
+  std::fs::write(
+    &script_path,
+    format!(
+      "# Generated by Divina, changes will be overwritten\n{}",
+      breakpoint
+    ),
+  )
+  .unwrap_or_else(|_| panic!("!! could not write '{}', check permissions", script_path));
+
+  divina_util::output::status(&format!(":: launching gdb on '{}'", binary));
+  log::debug!(
+    "running `gdb -q -x {} --args {} {}`",
+    script_path,
+    binary,
+    arguments.join(" ")
+  );
+
+  Command::new("gdb")
+    .args(["-q", "-x", &script_path, "--args", binary])
+    .args(arguments)
+    .status()
+    .unwrap_or_else(|_| {
+      divina_util::exit_with!(1, "!! could not launch gdb, perhaps it isn't installed ?");
+    })
+    .code()
+    .unwrap_or(1)
+}
@@ -16,9 +16,6 @@
   html_favicon_url = "https://emojipedia-us.s3.dualstack.us-west-1.amazonaws.com/thumbs/160/twitter/282/ribbon_1f380.png"
 )]
 
-// #[macro_use]
-// extern crate log;
-
 #[cfg(windows)]
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
@@ -27,21 +24,40 @@ static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 #[global_allocator]
 static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
+mod bench;
 mod cli;
+mod completions;
+mod debug;
+mod man;
+mod plugin;
+mod testing;
 
 use divina_compile::Compiler;
 use divina_config::Config;
+use structopt::clap::ArgMatches;
 
 #[derive(Default, Clone)]
 pub struct Divina {
+  matches:  ArgMatches<'static>,
   config:   Config,
   compiler: Compiler,
 }
 impl Divina {
+  /// Create a Divina which is driven by `matches`, the command line parsed by
+  /// `setup`
   #[must_use]
-  pub fn new() -> Self { Self::default() }
+  pub fn new(matches: ArgMatches<'static>) -> Self {
+    Self {
+      matches,
+      ..Self::default()
+    }
+  }
+
+  pub fn perform(&mut self) {
+    let matches = self.matches.clone();
 
-  pub fn perform(&mut self) { crate::cli::execute(self); }
+    crate::cli::execute(self, &matches);
+  }
 
   /// Prepare `self.config` for configuration
   pub fn new_config(&mut self) -> &mut Self {
@@ -49,8 +65,13 @@ impl Divina {
     self
   }
 
-  /// Configure `self.config`
-  pub fn configure_config(&mut self) { self.config.configure("Divina.lua"); }
+  /// Configure `self.config`, unless the selected subcommand doesn't need it
+  pub fn configure_config(&mut self) {
+    if crate::cli::requires_config(&self.matches) {
+      self.config.profile = Some(crate::cli::profile(&self.matches));
+      self.config.configure("Divina.lua");
+    }
+  }
 
   /// Print `self.config`
   pub fn print_config(&self) {
@@ -64,7 +85,11 @@ impl Divina {
 }
 
 /// Preliminary setup
-pub fn setup() {
+///
+/// Returns the parsed command line, which is only parsed here, and the handle
+/// of the logger, which must be kept alive for as long as Divina logs.
+#[must_use]
+pub fn setup() -> (ArgMatches<'static>, Option<flexi_logger::LoggerHandle>) {
   dotenv::dotenv().ok();
   human_panic::setup_panic!(Metadata {
     version:  env!("CARGO_PKG_VERSION").into(),
@@ -72,4 +97,35 @@ pub fn setup() {
     authors:  env!("CARGO_PKG_AUTHORS").into(),
     homepage: env!("CARGO_PKG_HOMEPAGE").into(),
   });
+
+  let matches = crate::cli::cli().get_matches();
+
+  crate::cli::configure_output(&matches);
+
+  let logger = setup_logger(&matches);
+
+  (matches, logger)
+}
+
+/// Log to standard error, at the level selected by `--debug` or `--trace`, or
+/// by the `DIVINA_LOG` environment variable using `env_logger`'s syntax, such
+/// as `DIVINA_LOG=divina_compile=debug`
+fn setup_logger(matches: &ArgMatches<'_>) -> Option<flexi_logger::LoggerHandle> {
+  let specification = crate::cli::log_specification(matches)
+    .map(ToString::to_string)
+    .or_else(|| std::env::var("DIVINA_LOG").ok())?;
+
+  flexi_logger::Logger::try_with_str(&specification)
+    .unwrap_or_else(|_| {
+      divina_util::exit_with!(
+        1,
+        "!! could not parse log specification '{}', perhaps `DIVINA_LOG` is malformed ?",
+        specification
+      );
+    })
+    .log_to_stderr()
+    .adaptive_format_for_stderr(flexi_logger::AdaptiveFormat::Default)
+    .start()
+    .map_err(|error| divina_util::output::error(&format!("!! could not start logger: {}", error)))
+    .ok()
 }
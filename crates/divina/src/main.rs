@@ -5,10 +5,10 @@ use divina::Divina;
 
 #[tokio::main]
 async fn main() {
-  // Preliminary pokes
-  divina::setup();
+  // Preliminary pokes, the logger stops once its handle is dropped
+  let (matches, _logger) = divina::setup();
 
-  let mut divina = Divina::new();
+  let mut divina = Divina::new(matches);
   // Store 'Divina.lua' configuration
   divina.new_config().configure_config();
   // Create a new compiler
@@ -0,0 +1,89 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use structopt::clap::{AppSettings, ErrorKind};
+
+/// Escape `text` so that roff prints it as is
+fn escape(text: &str) -> String {
+  let text = text.replace('\\', "\\e").replace('-', "\\-");
+
+  // A leading period or apostrophe would be read as a request
+  if text.starts_with('.') || text.starts_with('\'') {
+    format!("\\&{}", text)
+  } else {
+    text
+  }
+}
+
+/// Help of the subcommand at `path`, or of Divina itself if `path` is empty,
+/// from its usage onwards
+///
+/// clap 2 has no public accessors for an `App`'s arguments, so the help which
+/// `--help` prints is documented instead.
+fn help(path: &str) -> String {
+  let arguments = std::iter::once(env!("CARGO_PKG_NAME"))
+    .chain(path.split_whitespace())
+    .chain(std::iter::once("--help"));
+  let help = crate::cli::cli()
+    .global_setting(AppSettings::ColorNever)
+    .set_term_width(80)
+    .get_matches_from_safe(arguments)
+    .err()
+    .filter(|error| error.kind == ErrorKind::HelpDisplayed)
+    .map_or_else(
+      || {
+        panic!(
+          "!! could not generate help of `{}`, this *shouldn't* be possible",
+          path
+        )
+      },
+      |error| error.message,
+    );
+
+  help
+    .find("USAGE:")
+    .map_or_else(|| help.clone(), |usage| help[usage..].to_string())
+}
+
+/// Add `help` to `lines` as is, rather than letting roff fill it
+fn preformatted(help: &str, lines: &mut Vec<String>) {
+  lines.push(".nf".to_string());
+  lines.extend(help.trim_end().lines().map(escape));
+  lines.push(".fi".to_string());
+}
+
+/// Divina's manual page, generated from the CLI
+pub fn page() -> String {
+  let name = env!("CARGO_PKG_NAME");
+  let mut lines = vec![
+    format!(
+      ".TH {} 1 \"\" \"{} {}\"",
+      name.to_uppercase(),
+      name,
+      env!("CARGO_PKG_VERSION")
+    ),
+    ".SH NAME".to_string(),
+    format!("{} \\- {}", name, escape(env!("CARGO_PKG_DESCRIPTION"))),
+    ".SH SYNOPSIS".to_string(),
+    format!("\\fB{}\\fR [\\fIOPTIONS\\fR] \\fISUBCOMMAND\\fR", name),
+    ".SH OPTIONS".to_string(),
+  ];
+
+  preformatted(&help(""), &mut lines);
+  lines.push(".SH SUBCOMMANDS".to_string());
+
+  for (path, about) in crate::cli::SUBCOMMANDS {
+    lines.push(format!(".SS \"{} {}\"", name, escape(path)));
+
+    if !about.is_empty() {
+      lines.push(escape(about));
+    }
+
+    preformatted(&help(path), &mut lines);
+  }
+
+  lines.push(".SH AUTHORS".to_string());
+  lines.push(escape(env!("CARGO_PKG_AUTHORS")));
+
+  format!("{}\n", lines.join("\n"))
+}
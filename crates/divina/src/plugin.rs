@@ -0,0 +1,114 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// Prefix of a plugin's executable, `divina-flash` is run as `divina flash`
+const PREFIX: &str = "divina-";
+
+/// Where the metadata which plugins are passed is written
+const METADATA_PATH: &str = "out/metadata.json";
+
+fn is_executable(path: &std::path::Path) -> bool {
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path).map_or(false, |metadata| {
+      metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    })
+  }
+
+  #[cfg(not(unix))]
+  {
+    path.is_file()
+  }
+}
+
+/// Every plugin on `PATH` by name, the first of any which share a name wins
+/// like it would in a shell
+pub fn discover() -> BTreeMap<String, PathBuf> {
+  let mut plugins = BTreeMap::new();
+
+  for directory in std::env::split_paths(&std::env::var_os("PATH").unwrap_or_default()) {
+    // Directories which can't be read are skipped
+    for path in std::fs::read_dir(&directory)
+      .into_iter()
+      .flatten()
+      .flatten()
+      .map(|entry| entry.path())
+    {
+      let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+      if let Some(name) = name
+        .strip_prefix(PREFIX)
+        .map(|name| name.trim_end_matches(std::env::consts::EXE_SUFFIX))
+      {
+        if !name.is_empty() && !plugins.contains_key(name) && is_executable(&path) {
+          log::debug!("found plugin '{}' at '{}'", name, path.display());
+          plugins.insert(name.to_string(), path);
+        }
+      }
+    }
+  }
+
+  plugins
+}
+
+/// Run the plugin `name` with `arguments`, exiting with its exit code
+///
+/// Plugins are passed the path of Divina as `DIVINA` and the workspace root
+/// as `DIVINA_WORKSPACE_ROOT`. Within a workspace, they are also passed the
+/// path of its `divina metadata` as `DIVINA_METADATA`.
+pub fn run(divina: &mut crate::Divina, name: &str, arguments: &[&str]) -> ! {
+  let plugin = discover().remove(name).unwrap_or_else(|| {
+    divina_util::exit_with!(
+      1,
+      "!! no such subcommand '{}', perhaps you meant one from `divina --list` ?",
+      name
+    );
+  });
+  let workspace_root =
+    std::env::current_dir().expect("!! could not access the current directory, check permissions");
+  let invocation = divina_compile::Invocation {
+    program:   plugin.to_string_lossy().to_string(),
+    arguments: arguments.iter().map(ToString::to_string).collect(),
+  };
+  let mut command = invocation.command();
+
+  command.env("DIVINA_WORKSPACE_ROOT", &workspace_root);
+  log::trace!("set DIVINA_WORKSPACE_ROOT={}", workspace_root.display());
+
+  if let Ok(divina_path) = std::env::current_exe() {
+    log::trace!("set DIVINA={}", divina_path.display());
+    command.env("DIVINA", divina_path);
+  }
+
+  if std::path::Path::new("Divina.lua").exists() {
+    let metadata = divina
+      .compiler
+      .find_sources(divina.expose_config())
+      .metadata();
+
+    std::fs::create_dir_all("out/")
+      .expect("!! could not create directory 'out/', check permissions");
+    std::fs::write(METADATA_PATH, metadata)
+      .unwrap_or_else(|_| panic!("!! could not write '{}', check permissions", METADATA_PATH));
+    command.env("DIVINA_METADATA", workspace_root.join(METADATA_PATH));
+    log::trace!(
+      "set DIVINA_METADATA={}",
+      workspace_root.join(METADATA_PATH).display()
+    );
+  }
+
+  divina_util::output::verbose(&format!("   `{}`", invocation.command_line()));
+
+  let status = command.status().unwrap_or_else(|_| {
+    divina_util::exit_with!(1, "!! could not run plugin '{}'", plugin.display());
+  });
+
+  std::process::exit(crate::cli::exit_code(status));
+}
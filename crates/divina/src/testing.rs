@@ -0,0 +1,246 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use divina_compile::{TestCase, TestResult};
+
+/// How test results are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  Human,
+  Junit,
+  Tap,
+}
+impl Format {
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "human" => Some(Self::Human),
+      "junit" => Some(Self::Junit),
+      "tap" => Some(Self::Tap),
+      _ => None,
+    }
+  }
+}
+
+/// Print a test's outcome, along with why it failed
+///
+/// Failed tests are reported even if `--quiet` is passed.
+fn report(result: &TestResult) {
+  let print = if result.passed() {
+    divina_util::output::status
+  } else {
+    divina_util::output::print
+  };
+
+  print(&format!(
+    ":: {} @@ test '{}' ... {} ({:.2}s)",
+    result.package,
+    result.name,
+    if !result.passed() {
+      "FAILED"
+    } else if result.blessed {
+      "blessed"
+    } else {
+      "ok"
+    },
+    result.duration
+  ));
+
+  for failure in &result.failures {
+    for line in failure.lines() {
+      print(&format!("   {}", line));
+    }
+  }
+}
+
+fn escape_xml(text: &str) -> String {
+  text
+    .chars()
+    .map(|c| {
+      match c {
+        '&' => "&amp;".to_string(),
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '"' => "&quot;".to_string(),
+        '\'' => "&apos;".to_string(),
+        // Control characters besides whitespace are not allowed in XML 1.0
+        c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => String::new(),
+        c => c.to_string(),
+      }
+    })
+    .collect()
+}
+
+/// A JUnit-style XML report with a `testsuite` for each package
+fn junit(results: &[TestResult]) -> String {
+  let mut packages: Vec<&str> = Vec::new();
+
+  for result in results {
+    if !packages.contains(&result.package.as_str()) {
+      packages.push(&result.package);
+    }
+  }
+
+  let summary = |results: &[&TestResult]| {
+    format!(
+      "tests=\"{}\" failures=\"{}\" time=\"{:.3}\"",
+      results.len(),
+      results.iter().filter(|result| !result.passed()).count(),
+      results.iter().map(|result| result.duration).sum::<f64>()
+    )
+  };
+  let mut lines = vec![
+    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>".to_string(),
+    format!(
+      "<testsuites name=\"divina\" {}>",
+      summary(&results.iter().collect::<Vec<_>>())
+    ),
+  ];
+
+  for package in packages {
+    let suite = results
+      .iter()
+      .filter(|result| result.package == package)
+      .collect::<Vec<_>>();
+
+    lines.push(format!(
+      "  <testsuite name=\"{}\" {}>",
+      escape_xml(package),
+      summary(&suite)
+    ));
+
+    for result in suite {
+      lines.push(format!(
+        "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">",
+        escape_xml(&result.name),
+        escape_xml(&result.package),
+        result.duration
+      ));
+
+      if !result.passed() {
+        lines.push(format!(
+          "      <failure message=\"{}\">{}</failure>",
+          escape_xml(result.failures[0].lines().next().unwrap_or_default()),
+          escape_xml(&result.failures.join("\n"))
+        ));
+      }
+
+      lines.push(format!(
+        "      <system-out>{}</system-out>",
+        escape_xml(&result.stdout)
+      ));
+      lines.push(format!(
+        "      <system-err>{}</system-err>",
+        escape_xml(&result.stderr)
+      ));
+      lines.push("    </testcase>".to_string());
+    }
+
+    lines.push("  </testsuite>".to_string());
+  }
+
+  lines.push("</testsuites>".to_string());
+
+  format!("{}\n", lines.join("\n"))
+}
+
+/// A TAP version 13 report, with a YAML block explaining each failure
+fn tap(results: &[TestResult]) -> String {
+  let mut lines = vec![
+    "TAP version 13".to_string(),
+    format!("1..{}", results.len()),
+  ];
+  // Literal block scalars keep the output intact, whatever it contains
+  let block = |lines: &mut Vec<String>, key: &str, text: &str| {
+    lines.push(format!("  {}: |", key));
+    lines.extend(text.lines().map(|line| format!("    {}", line)));
+  };
+
+  for (index, result) in results.iter().enumerate() {
+    lines.push(format!(
+      "{} {} - {} {}",
+      if result.passed() { "ok" } else { "not ok" },
+      index + 1,
+      result.package,
+      result.name
+    ));
+
+    if !result.passed() {
+      lines.push("  ---".to_string());
+      block(&mut lines, "message", &result.failures.join("\n"));
+      lines.push(format!("  duration_ms: {:.3}", result.duration * 1000.0));
+
+      if !result.stdout.is_empty() {
+        block(&mut lines, "stdout", &result.stdout);
+      }
+
+      if !result.stderr.is_empty() {
+        block(&mut lines, "stderr", &result.stderr);
+      }
+
+      lines.push("  ...".to_string());
+    }
+  }
+
+  format!("{}\n", lines.join("\n"))
+}
+
+/// Run every test, reporting their results in `format` to `output`, or to
+/// standard output if no output is given
+///
+/// Exits if any test failed.
+pub fn run(cases: &[TestCase], bless: bool, format: Format, output: Option<&str>) {
+  if format == Format::Human {
+    divina_util::output::status(&format!(
+      ":: running {} test{}",
+      cases.len(),
+      if cases.len() == 1 { "" } else { "s" }
+    ));
+  }
+
+  let results = cases
+    .iter()
+    .map(|case| {
+      let result = case.run(bless);
+
+      if format == Format::Human {
+        report(&result);
+      }
+
+      result
+    })
+    .collect::<Vec<_>>();
+  let failed = results.iter().filter(|result| !result.passed()).count();
+  let report = match format {
+    Format::Human => None,
+    Format::Junit => Some(junit(&results)),
+    Format::Tap => Some(tap(&results)),
+  };
+
+  match (report, output) {
+    (Some(report), Some(output)) => {
+      std::fs::write(output, report)
+        .unwrap_or_else(|_| panic!("!! could not write '{}', check permissions", output));
+      divina_util::output::status(&format!(":: wrote test report '{}'", output));
+    }
+    (Some(report), None) => print!("{}", report),
+    (None, _) =>
+      divina_util::output::status(&format!(
+        ":: {} passed, {} failed",
+        results.len() - failed,
+        failed
+      )),
+  }
+
+  if failed > 0 {
+    if format == Format::Human || output.is_some() {
+      divina_util::exit_with!(
+        1,
+        "!! {} test{} failed",
+        failed,
+        if failed == 1 { "" } else { "s" }
+      );
+    } else {
+      divina_util::exit_with!(1);
+    }
+  }
+}
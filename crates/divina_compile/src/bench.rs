@@ -0,0 +1,251 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Benchmarks of a package's binary, declared in `Package.benches`
+
+use std::{
+  collections::BTreeMap,
+  fs,
+  io::Write,
+  process::{Command, Stdio},
+  time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Directory which baselines are saved into
+const BASELINE_DIRECTORY: &str = "out/bench/";
+
+/// A benchmark, resolved against its package's binary
+#[derive(Debug, Clone)]
+pub struct BenchCase {
+  pub package:    String,
+  pub name:       String,
+  pub binary:     String,
+  pub args:       Vec<String>,
+  pub stdin:      Option<String>,
+  pub iterations: usize,
+  pub warmup:     usize,
+}
+impl BenchCase {
+  /// Key of the benchmark within a baseline
+  #[must_use]
+  pub fn key(&self) -> String { format!("{}/{}", self.package, self.name) }
+
+  /// Run the binary once, returning how long it took in seconds
+  fn sample(&self) -> Result<f64, String> {
+    let started = Instant::now();
+    let mut child = Command::new(&self.binary)
+      .args(&self.args)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .spawn()
+      .map_err(|error| format!("could not run '{}': {}", self.binary, error))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+      // A binary which exits without reading its input closes the pipe
+      let _ = stdin.write_all(self.stdin.as_deref().unwrap_or_default().as_bytes());
+    }
+
+    let status = child
+      .wait()
+      .map_err(|error| format!("could not wait on '{}': {}", self.binary, error))?;
+    let elapsed = started.elapsed().as_secs_f64();
+
+    log::trace!(
+      "`{}` exited with {} after {:.6}s",
+      self.binary,
+      status,
+      elapsed
+    );
+
+    if status.success() {
+      Ok(elapsed)
+    } else {
+      Err(format!(
+        "'{}' exited unsuccessfully with {}",
+        self.binary, status
+      ))
+    }
+  }
+
+  /// Run the warmup runs followed by the timed runs
+  ///
+  /// # Errors
+  /// if the binary could not be run or exited unsuccessfully
+  pub fn run(&self) -> Result<Statistics, String> {
+    log::debug!(
+      "{} @@ running bench '{}' with {} warmup and {} timed runs of `{}`",
+      self.package,
+      self.name,
+      self.warmup,
+      self.iterations,
+      self.binary
+    );
+
+    for _ in 0..self.warmup {
+      self.sample()?;
+    }
+
+    let samples = (0..self.iterations)
+      .map(|_| self.sample())
+      .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Statistics::new(samples))
+  }
+}
+
+/// Summary of a benchmark's timed runs, in seconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Statistics {
+  pub iterations: usize,
+  pub mean:       f64,
+  pub median:     f64,
+  pub stddev:     f64,
+  pub min:        f64,
+  pub max:        f64,
+  /// Runs outside of Tukey's fences, 1.5 interquartile ranges beyond the
+  /// first and third quartiles
+  pub outliers:   usize,
+}
+impl Statistics {
+  #[allow(clippy::cast_precision_loss)]
+  fn new(mut samples: Vec<f64>) -> Self {
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let count = samples.len().max(1) as f64;
+    let mean = samples.iter().sum::<f64>() / count;
+    let variance = samples
+      .iter()
+      .map(|sample| (sample - mean).powi(2))
+      .sum::<f64>()
+      / (count - 1.0).max(1.0);
+    let first_quartile = percentile(&samples, 0.25);
+    let third_quartile = percentile(&samples, 0.75);
+    let fence = 1.5 * (third_quartile - first_quartile);
+
+    Self {
+      iterations: samples.len(),
+      mean,
+      median: percentile(&samples, 0.5),
+      stddev: variance.sqrt(),
+      min: samples.first().copied().unwrap_or_default(),
+      max: samples.last().copied().unwrap_or_default(),
+      outliers: samples
+        .iter()
+        .filter(|sample| **sample < first_quartile - fence || **sample > third_quartile + fence)
+        .count(),
+    }
+  }
+
+  /// Relative change of the median from `baseline`'s, e.g., `0.05` for 5%
+  /// slower
+  #[must_use]
+  pub fn change(&self, baseline: &Self) -> f64 {
+    if baseline.median == 0.0 {
+      0.0
+    } else {
+      (self.median - baseline.median) / baseline.median
+    }
+  }
+}
+
+/// Linearly interpolated percentile of sorted `samples`
+#[allow(
+  clippy::cast_precision_loss,
+  clippy::cast_possible_truncation,
+  clippy::cast_sign_loss
+)]
+fn percentile(samples: &[f64], fraction: f64) -> f64 {
+  if samples.is_empty() {
+    return 0.0;
+  }
+
+  let position = fraction * (samples.len() - 1) as f64;
+  let lower = position.floor() as usize;
+  let upper = position.ceil() as usize;
+
+  (samples[upper] - samples[lower]).mul_add(position - lower as f64, samples[lower])
+}
+
+fn baseline_path(name: &str) -> String { format!("{}{}.json", BASELINE_DIRECTORY, name) }
+
+/// Save the statistics of every benchmark, keyed by `BenchCase::key`, as the
+/// baseline `name`
+///
+/// # Panics
+/// if caller has insufficient permissions to write the baseline
+pub fn save_baseline(name: &str, statistics: &BTreeMap<String, Statistics>) {
+  fs::create_dir_all(BASELINE_DIRECTORY).unwrap_or_else(|_| {
+    panic!(
+      "!! could not create directory '{}', check permissions",
+      BASELINE_DIRECTORY
+    )
+  });
+  fs::write(
+    baseline_path(name),
+    serde_json::to_string_pretty(statistics)
+      .expect("!! could not serialize baseline, this *shouldn't* be possible"),
+  )
+  .unwrap_or_else(|_| {
+    panic!(
+      "!! could not write '{}', check permissions",
+      baseline_path(name)
+    )
+  });
+}
+
+/// Load the baseline `name`, if it has been saved
+#[must_use]
+pub fn load_baseline(name: &str) -> Option<BTreeMap<String, Statistics>> {
+  serde_json::from_str(&fs::read_to_string(baseline_path(name)).ok()?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Statistics;
+
+  fn close(actual: f64, expected: f64) -> bool { (actual - expected).abs() < 1e-9 }
+
+  #[test]
+  fn statistics_summarise_samples() {
+    let statistics = Statistics::new(vec![4.0, 1.0, 3.0, 2.0]);
+
+    assert_eq!(statistics.iterations, 4);
+    assert!(close(statistics.mean, 2.5));
+    assert!(close(statistics.median, 2.5));
+    // Sample standard deviation, with Bessel's correction
+    assert!(close(statistics.stddev, (5.0_f64 / 3.0).sqrt()));
+    assert!(close(statistics.min, 1.0));
+    assert!(close(statistics.max, 4.0));
+    assert_eq!(statistics.outliers, 0);
+  }
+
+  #[test]
+  fn statistics_count_outliers() {
+    let statistics = Statistics::new(vec![1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 10.0]);
+
+    assert_eq!(statistics.outliers, 1);
+    assert!(close(statistics.median, 1.0));
+  }
+
+  #[test]
+  fn statistics_of_no_samples_are_zero() {
+    let statistics = Statistics::new(Vec::new());
+
+    assert_eq!(statistics.iterations, 0);
+    assert!(close(statistics.mean, 0.0));
+    assert!(close(statistics.median, 0.0));
+    assert!(close(statistics.stddev, 0.0));
+  }
+
+  #[test]
+  fn change_is_relative_to_the_baseline_median() {
+    let baseline = Statistics::new(vec![2.0, 2.0, 2.0]);
+
+    assert!(close(Statistics::new(vec![2.2]).change(&baseline), 0.1));
+    assert!(close(Statistics::new(vec![1.0]).change(&baseline), -0.5));
+    assert!(close(baseline.change(&Statistics::new(vec![0.0])), 0.0));
+  }
+}
@@ -0,0 +1,39 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Clang's JSON compilation database, as understood by asm-lsp and other
+//! editor tooling
+
+use serde::Serialize;
+
+use crate::plan::{Step, StepKind};
+
+#[derive(Debug, Serialize)]
+struct CompileCommand<'a> {
+  directory: &'a str,
+  arguments: Vec<&'a str>,
+  file:      &'a str,
+  output:    &'a str,
+}
+
+/// Render the compile steps of `steps` as a compilation database, relative to
+/// `directory`
+pub fn render(directory: &str, steps: &[Step]) -> String {
+  let commands = steps
+    .iter()
+    .filter(|step| step.kind == StepKind::Compile)
+    .map(|step| {
+      CompileCommand {
+        directory,
+        arguments: std::iter::once(step.invocation.program.as_str())
+          .chain(step.invocation.arguments.iter().map(String::as_str))
+          .collect(),
+        file: &step.inputs[0],
+        output: &step.output,
+      }
+    })
+    .collect::<Vec<_>>();
+
+  serde_json::to_string_pretty(&commands)
+    .expect("!! could not serialize compilation database, this *shouldn't* be possible")
+}
@@ -0,0 +1,175 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! An include file of `Package.constants` and the layouts of
+//! `Package.structs`
+
+use std::collections::BTreeMap;
+
+use divina_config::StructField;
+
+/// Name of the include file, within the generated directory
+pub const FILE_NAME: &str = "constants.inc";
+
+/// How a constant is defined for an assembler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+  /// `%define NAME value`, for NASM and YASM
+  Define,
+  /// `NAME equ value`
+  Equ,
+  /// `.set NAME, value`, for GNU as
+  Set,
+}
+impl Syntax {
+  #[must_use]
+  pub fn for_compiler(compiler: &str) -> Self {
+    match compiler {
+      "nasm" | "yasm" => Self::Define,
+      "as" | "gas" => Self::Set,
+      _ => Self::Equ,
+    }
+  }
+
+  const fn comment(self) -> &'static str {
+    match self {
+      Self::Define | Self::Equ => ";",
+      Self::Set => "#",
+    }
+  }
+
+  fn define(self, name: &str, value: &str) -> String {
+    match self {
+      Self::Define => format!("%define {} {}", name, value),
+      Self::Equ => format!("{} equ {}", name, value),
+      Self::Set => format!(".set {}, {}", name, value),
+    }
+  }
+}
+
+/// Render `constants`, followed by the offset of every field of `structs` as
+/// `<struct>_<field>` and the size of each struct as `<struct>_size`
+pub fn render(
+  package: &str,
+  syntax: Syntax,
+  constants: &BTreeMap<String, String>,
+  structs: &BTreeMap<String, Vec<StructField>>,
+) -> String {
+  // The heading of generated build files, commented for the assembler
+  let mut lines = vec![crate::generate::HEADER.replacen('#', syntax.comment(), 1)];
+
+  lines.extend(
+    constants
+      .iter()
+      .map(|(name, value)| syntax.define(name, value)),
+  );
+
+  for (name, fields) in structs {
+    let mut offset = 0;
+
+    lines.push(String::new());
+
+    for (index, field) in fields.iter().enumerate() {
+      let field_name = field.name.as_ref().unwrap_or_else(|| {
+        divina_util::exit_with!(
+          1,
+          "!! {} @@ could not access `Package.structs.{}[{}].name`, perhaps you've forgotten to \
+           assign it ?",
+          package,
+          name,
+          index + 1
+        );
+      });
+      let size = field.size.unwrap_or_else(|| {
+        divina_util::exit_with!(
+          1,
+          "!! {} @@ could not access `Package.structs.{}.{}.size`, perhaps you've forgotten to \
+           assign it ?",
+          package,
+          name,
+          field_name
+        );
+      });
+
+      lines.push(syntax.define(&format!("{}_{}", name, field_name), &offset.to_string()));
+      offset += size;
+    }
+
+    lines.push(syntax.define(&format!("{}_size", name), &offset.to_string()));
+  }
+
+  format!("{}\n", lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use divina_config::StructField;
+
+  use super::{render, Syntax};
+
+  fn constants() -> BTreeMap<String, String> {
+    let mut constants = BTreeMap::new();
+
+    constants.insert("BUFFER_SIZE".to_string(), "4096".to_string());
+    constants.insert("EXIT_SUCCESS".to_string(), "0".to_string());
+
+    constants
+  }
+
+  fn field(name: &str, size: usize) -> StructField {
+    StructField {
+      name: Some(name.to_string()),
+      size: Some(size),
+    }
+  }
+
+  #[test]
+  fn render_defines_constants_for_nasm_and_yasm() {
+    assert_eq!(Syntax::for_compiler("nasm"), Syntax::Define);
+    assert_eq!(Syntax::for_compiler("yasm"), Syntax::Define);
+    assert_eq!(
+      render("package", Syntax::Define, &constants(), &BTreeMap::new()),
+      "; Generated by Divina, changes will be overwritten\n%define BUFFER_SIZE 4096\n%define \
+       EXIT_SUCCESS 0\n"
+    );
+  }
+
+  #[test]
+  fn render_equates_constants() {
+    assert_eq!(Syntax::for_compiler("fasm"), Syntax::Equ);
+    assert_eq!(
+      render("package", Syntax::Equ, &constants(), &BTreeMap::new()),
+      "; Generated by Divina, changes will be overwritten\nBUFFER_SIZE equ 4096\nEXIT_SUCCESS equ \
+       0\n"
+    );
+  }
+
+  #[test]
+  fn render_sets_constants_for_gnu_as() {
+    assert_eq!(Syntax::for_compiler("as"), Syntax::Set);
+    assert_eq!(
+      render("package", Syntax::Set, &constants(), &BTreeMap::new()),
+      "# Generated by Divina, changes will be overwritten\n.set BUFFER_SIZE, 4096\n.set \
+       EXIT_SUCCESS, 0\n"
+    );
+  }
+
+  #[test]
+  fn render_lays_out_struct_fields_without_padding() {
+    let mut structs = BTreeMap::new();
+
+    structs.insert(
+      "point".to_string(),
+      vec![field("x", 4), field("y", 4), field("tag", 1)],
+    );
+    structs.insert("empty".to_string(), Vec::new());
+
+    assert_eq!(
+      render("package", Syntax::Define, &BTreeMap::new(), &structs),
+      "; Generated by Divina, changes will be overwritten\n\n%define empty_size 0\n\n%define \
+       point_x 0\n%define point_y 4\n%define point_tag 8\n%define point_size 9\n"
+    );
+  }
+}
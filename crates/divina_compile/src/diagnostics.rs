@@ -0,0 +1,302 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Parsing and rendering of NASM, YASM and GNU as diagnostics
+
+use std::fmt;
+
+use divina_util::output::Style;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+  Error,
+  Warning,
+  Note,
+}
+impl Severity {
+  /// Markers which precede a message, all assemblers are matched
+  /// case-insensitively as GNU as capitalises them
+  const MARKERS: [(&'static str, Self); 6] = [
+    ("fatal", Self::Error),
+    ("panic", Self::Error),
+    ("error", Self::Error),
+    ("warning", Self::Warning),
+    ("note", Self::Note),
+    ("info", Self::Note),
+  ];
+
+  const fn style(self) -> Style {
+    match self {
+      Self::Error => Style::Error,
+      Self::Warning => Style::Warning,
+      Self::Note => Style::Bold,
+    }
+  }
+}
+impl fmt::Display for Severity {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Self::Error => "error",
+      Self::Warning => "warning",
+      Self::Note => "note",
+    })
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+  pub file:     Option<String>,
+  pub line:     Option<usize>,
+  pub severity: Severity,
+  pub message:  String,
+  /// Other locations which reported the same message, e.g., every expansion
+  /// of a faulty macro
+  pub repeated: Vec<(String, usize)>,
+}
+impl Diagnostic {
+  /// Parse a single line of assembler output, such as
+  /// `Main.asm:12: error: symbol 'foo' not defined`
+  #[must_use]
+  pub fn parse(line: &str) -> Option<Self> {
+    let line = line.trim_end();
+    // ASCII lowercasing keeps byte offsets intact
+    let lowercase = line.to_ascii_lowercase();
+    let (position, marker, severity) = Severity::MARKERS
+      .iter()
+      .filter_map(|(marker, severity)| {
+        lowercase
+          .find(&format!(": {}: ", marker))
+          .map(|position| (position, *marker, *severity))
+      })
+      .min_by_key(|(position, ..)| *position)?;
+    let location = &line[..position];
+    let message = line[position + marker.len() + 4..].trim().to_string();
+    // Lines without a line number, such as `nasm: fatal: ...`, are prefixed
+    // with the assembler's name rather than a file
+    let (file, line) = match location.rsplit_once(':') {
+      Some((file, line)) if !file.is_empty() =>
+        line
+          .trim()
+          .parse()
+          .map_or((None, None), |line| (Some(file.to_string()), Some(line))),
+      _ => (None, None),
+    };
+
+    Some(Self {
+      file,
+      line,
+      severity,
+      message,
+      repeated: Vec::new(),
+    })
+  }
+
+  /// Parse every diagnostic in `output`, folding diagnostics which repeat a
+  /// previous message into it
+  #[must_use]
+  pub fn parse_all(output: &str) -> Vec<Self> {
+    let mut diagnostics: Vec<Self> = Vec::new();
+
+    for diagnostic in output.lines().filter_map(Self::parse) {
+      if let Some(original) = diagnostics.iter_mut().find(|original| {
+        original.severity == diagnostic.severity && original.message == diagnostic.message
+      }) {
+        if let (Some(file), Some(line)) = (diagnostic.file, diagnostic.line) {
+          if original.file.as_ref() != Some(&file) || original.line != Some(line) {
+            original.repeated.push((file, line));
+          }
+        }
+      } else {
+        diagnostics.push(diagnostic);
+      }
+    }
+
+    diagnostics
+  }
+
+  /// Render the diagnostic along with a snippet of its source
+  #[must_use]
+  pub fn render(&self, color: bool) -> String {
+    let paint = |style: Style, text: &str| style.paint(text, color);
+    let mut lines = vec![format!(
+      "{}{}",
+      paint(self.severity.style(), &format!("{}:", self.severity)),
+      paint(Style::Bold, &format!(" {}", self.message))
+    )];
+
+    if let (Some(file), Some(line)) = (&self.file, self.line) {
+      let snippet = std::fs::read_to_string(file).ok().and_then(|contents| {
+        contents
+          .lines()
+          .nth(line.saturating_sub(1))
+          .map(ToString::to_string)
+      });
+      let gutter = " ".repeat(line.to_string().len());
+
+      lines.push(format!(
+        "{}{} {}:{}",
+        gutter,
+        paint(Style::Accent, "-->"),
+        file,
+        line
+      ));
+
+      if let Some(snippet) = snippet {
+        lines.push(format!("{} {}", gutter, paint(Style::Accent, "|")));
+        lines.push(format!(
+          "{} {} {}",
+          paint(Style::Accent, &line.to_string()),
+          paint(Style::Accent, "|"),
+          snippet
+        ));
+        lines.push(format!("{} {}", gutter, paint(Style::Accent, "|")));
+      }
+
+      if !self.repeated.is_empty() {
+        lines.push(format!(
+          "{} {} also reported at {}",
+          gutter,
+          paint(Style::Accent, "= note:"),
+          self
+            .repeated
+            .iter()
+            .map(|(file, line)| format!("{}:{}", file, line))
+            .collect::<Vec<_>>()
+            .join(", ")
+        ));
+      }
+    }
+
+    lines.join("\n")
+  }
+}
+
+/// Error and warning counts of a package's build
+#[derive(Debug, Clone)]
+pub struct Summary {
+  pub package:  String,
+  pub errors:   usize,
+  pub warnings: usize,
+  /// Whether the assembler failed on any source, even if it did not report
+  /// an error which could be parsed, or the linker failed
+  pub failed:   bool,
+}
+impl Summary {
+  #[must_use]
+  pub fn new(package: &str, diagnostics: &[Diagnostic], failed: bool) -> Self {
+    let count = |severity| {
+      diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == severity)
+        .map(|diagnostic| diagnostic.repeated.len() + 1)
+        .sum()
+    };
+
+    Self {
+      package: package.to_string(),
+      errors: count(Severity::Error),
+      warnings: count(Severity::Warning),
+      failed,
+    }
+  }
+}
+impl fmt::Display for Summary {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{} error{}, {} warning{}",
+      self.errors,
+      if self.errors == 1 { "" } else { "s" },
+      self.warnings,
+      if self.warnings == 1 { "" } else { "s" }
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Diagnostic, Severity, Summary};
+
+  #[test]
+  fn parse_reads_a_located_diagnostic() {
+    assert_eq!(
+      Diagnostic::parse("Main.asm:12: error: symbol `foo' not defined"),
+      Some(Diagnostic {
+        file:     Some("Main.asm".to_string()),
+        line:     Some(12),
+        severity: Severity::Error,
+        message:  "symbol `foo' not defined".to_string(),
+        repeated: Vec::new(),
+      })
+    );
+  }
+
+  #[test]
+  fn parse_matches_markers_case_insensitively() {
+    // GNU as capitalises its markers
+    let diagnostic = Diagnostic::parse("Main.s:3: Warning: end of file not at end of a line")
+      .expect("a GNU as warning should parse");
+
+    assert_eq!(diagnostic.severity, Severity::Warning);
+    assert_eq!(diagnostic.line, Some(3));
+    assert_eq!(diagnostic.message, "end of file not at end of a line");
+  }
+
+  #[test]
+  fn parse_reads_an_unlocated_diagnostic() {
+    let diagnostic = Diagnostic::parse("nasm: fatal: unable to open input file `Missing.asm'")
+      .expect("should parse");
+
+    assert_eq!(diagnostic.severity, Severity::Error);
+    assert_eq!(diagnostic.file, None);
+    assert_eq!(diagnostic.line, None);
+    assert_eq!(
+      diagnostic.message,
+      "unable to open input file `Missing.asm'"
+    );
+  }
+
+  #[test]
+  fn parse_uses_the_first_marker() {
+    let diagnostic =
+      Diagnostic::parse("Main.asm:4: warning: label alone on a line: error: is not an error")
+        .expect("should parse");
+
+    assert_eq!(diagnostic.severity, Severity::Warning);
+    assert_eq!(
+      diagnostic.message,
+      "label alone on a line: error: is not an error"
+    );
+  }
+
+  #[test]
+  fn parse_ignores_other_output() {
+    assert_eq!(Diagnostic::parse(""), None);
+    assert_eq!(
+      Diagnostic::parse("collect2: ld returned 1 exit status"),
+      None
+    );
+    assert_eq!(Diagnostic::parse("an error occurred"), None);
+  }
+
+  #[test]
+  fn parse_all_folds_repeated_messages() {
+    let diagnostics = Diagnostic::parse_all(
+      "Main.asm:3: error: invalid combination of opcode and operands\nMain.asm:3: error: invalid \
+       combination of opcode and operands\nMain.asm:9: error: invalid combination of opcode and \
+       operands\nMain.asm:10: warning: byte data exceeds bounds\n",
+    );
+
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].line, Some(3));
+    assert_eq!(diagnostics[0].repeated, vec![("Main.asm".to_string(), 9)]);
+    assert_eq!(diagnostics[1].severity, Severity::Warning);
+
+    let summary = Summary::new("greet", &diagnostics, true);
+
+    assert_eq!((summary.errors, summary.warnings), (2, 1));
+    assert_eq!(summary.to_string(), "2 errors, 1 warning");
+  }
+}
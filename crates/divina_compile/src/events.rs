@@ -0,0 +1,60 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::Serialize;
+
+use crate::Diagnostic;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+  /// `::`-prefixed status lines and rendered diagnostics
+  Human,
+  /// One JSON object per line for every `Event`
+  Json,
+}
+impl MessageFormat {
+  #[must_use]
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "human" => Some(Self::Human),
+      "json" => Some(Self::Json),
+      _ => None,
+    }
+  }
+}
+impl Default for MessageFormat {
+  fn default() -> Self { Self::Human }
+}
+
+/// Build progress, emitted by `Compiler` with `MessageFormat::Json`
+#[derive(Debug, Serialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum Event<'a> {
+  PackageStarted {
+    package: &'a str,
+    profile: &'a str,
+  },
+  SourceCompiled {
+    package:     &'a str,
+    source:      &'a str,
+    object:      &'a str,
+    success:     bool,
+    /// Seconds spent in the assembler
+    duration:    f64,
+    diagnostics: &'a [Diagnostic],
+  },
+  PackageLinked {
+    package:  &'a str,
+    success:  bool,
+    /// Seconds spent in the linker
+    duration: f64,
+    output:   &'a str,
+  },
+  Artifact {
+    package: &'a str,
+    path:    &'a str,
+  },
+  BuildFinished {
+    success: bool,
+  },
+}
@@ -0,0 +1,160 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Standalone Ninja and Make build files which run the same commands as
+//! `divina build`
+
+use crate::plan::{Step, StepKind};
+
+/// Heading of every generated build file, used to avoid overwriting build
+/// files which Divina did not generate
+pub const HEADER: &str = "# Generated by Divina, changes will be overwritten";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generator {
+  Ninja,
+  Make,
+}
+impl Generator {
+  #[must_use]
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "ninja" => Some(Self::Ninja),
+      "make" => Some(Self::Make),
+      _ => None,
+    }
+  }
+
+  /// File which the build file is conventionally written to
+  #[must_use]
+  pub const fn file_name(self) -> &'static str {
+    match self {
+      Self::Ninja => "build.ninja",
+      Self::Make => "Makefile",
+    }
+  }
+
+  pub(crate) fn render(self, steps: &[Step]) -> String {
+    match self {
+      Self::Ninja => ninja(steps),
+      Self::Make => make(steps),
+    }
+  }
+}
+
+/// Binaries which are built by default
+fn binaries(steps: &[Step]) -> impl Iterator<Item = &Step> {
+  steps.iter().filter(|step| step.kind == StepKind::Link)
+}
+
+fn description(step: &Step) -> String {
+  match step.kind {
+    StepKind::Compile => format!("{} @@ compiling source '{}'", step.package, step.inputs[0]),
+    StepKind::Link => format!("{} @@ linking '{}'", step.package, step.output),
+  }
+}
+
+fn ninja(steps: &[Step]) -> String {
+  // Ninja only treats `$`, spaces, colons and newlines specially in paths
+  let escape_path = |path: &str| {
+    path
+      .replace('$', "$$")
+      .replace(' ', "$ ")
+      .replace(':', "$:")
+  };
+  let mut lines = vec![
+    HEADER.to_string(),
+    String::new(),
+    "rule divina".to_string(),
+    "  command = $command".to_string(),
+    "  description = $description".to_string(),
+  ];
+
+  for step in steps {
+    lines.push(String::new());
+    lines.push(format!(
+      "build {}: divina {}",
+      escape_path(&step.output),
+      step
+        .inputs
+        .iter()
+        .map(|input| escape_path(input))
+        .collect::<Vec<_>>()
+        .join(" ")
+    ));
+    lines.push(format!(
+      "  command = {}",
+      step.invocation.command_line().replace('$', "$$")
+    ));
+    lines.push(format!(
+      "  description = {}",
+      description(step).replace('$', "$$")
+    ));
+  }
+
+  lines.push(String::new());
+  lines.push(format!(
+    "default {}",
+    binaries(steps)
+      .map(|step| escape_path(&step.output))
+      .collect::<Vec<_>>()
+      .join(" ")
+  ));
+
+  format!("{}\n", lines.join("\n"))
+}
+
+fn make(steps: &[Step]) -> String {
+  // Make has no way to quote a space in a target, only `$` and `#` may be
+  // escaped
+  let escape_path = |path: &str| path.replace('$', "$$").replace('#', "\\#");
+  let mut lines = vec![
+    HEADER.to_string(),
+    String::new(),
+    ".PHONY: all clean".to_string(),
+    String::new(),
+    format!(
+      "all: {}",
+      binaries(steps)
+        .map(|step| escape_path(&step.output))
+        .collect::<Vec<_>>()
+        .join(" ")
+    ),
+  ];
+
+  for step in steps {
+    lines.push(String::new());
+    lines.push(format!(
+      "{}: {}",
+      escape_path(&step.output),
+      step
+        .inputs
+        .iter()
+        .map(|input| escape_path(input))
+        .collect::<Vec<_>>()
+        .join(" ")
+    ));
+    lines.push(format!(
+      "\t@echo ':: {}'",
+      description(step).replace('\'', "")
+    ));
+    lines.push("\t@mkdir -p $(@D)".to_string());
+    lines.push(format!(
+      "\t{}",
+      step.invocation.command_line().replace('$', "$$")
+    ));
+  }
+
+  lines.push(String::new());
+  lines.push("clean:".to_string());
+  lines.push(format!(
+    "\trm -f {}",
+    steps
+      .iter()
+      .map(|step| escape_path(&step.output))
+      .collect::<Vec<_>>()
+      .join(" ")
+  ));
+
+  format!("{}\n", lines.join("\n"))
+}
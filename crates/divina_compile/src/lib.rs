@@ -17,50 +17,699 @@
   html_favicon_url = "https://emojipedia-us.s3.dualstack.us-west-1.amazonaws.com/thumbs/160/twitter/282/ribbon_1f380.png"
 )]
 
-use std::fs;
+use std::{collections::BTreeMap, fs, time::Instant};
 
-use divina_config::Arch;
+mod bench;
+mod compdb;
+mod constants;
+mod diagnostics;
+mod events;
+mod generate;
+mod memcheck;
+mod metadata;
+mod plan;
+mod profile;
+mod testing;
+mod unit;
+
+pub use bench::{load_baseline, save_baseline, BenchCase, Statistics};
+pub use diagnostics::{Diagnostic, Severity, Summary};
+use divina_config::{Arch, PackageType};
+pub use events::{Event, MessageFormat};
+pub use generate::{Generator, HEADER as GENERATED_HEADER};
+pub use memcheck::Memcheck;
+pub use plan::{Invocation, Step, StepKind};
+use profile::Profile;
+pub use profile::DEFAULT_PROFILE;
+pub use testing::{TestCase, TestResult};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[derive(Debug, Clone)]
 struct Source {
   filename: String,
   path:     String,
 }
+impl Source {
+  /// Object file which the source is compiled into
+  fn object_path(&self, out_directory: &str) -> String {
+    format!(
+      "{}{}.{}",
+      out_directory,
+      self.filename,
+      if cfg!(windows) { "obj" } else { "o" }
+    )
+  }
+}
 
 #[derive(Debug, Clone)]
 struct Package {
-  name:          String,
-  sources:       Vec<Source>,
-  arch:          Arch,
-  compiler:      String,
+  name:            String,
+  version:         String,
+  sources:         Vec<Source>,
+  package_type:    PackageType,
+  arch:            Arch,
+  compiler:        String,
   #[allow(unused)]
-  visual_studio: Option<String>,
+  visual_studio:   Option<String>,
+  include_dirs:    Vec<String>,
+  defines:         BTreeMap<String, String>,
+  compile_options: Vec<String>,
+  profile:         Profile,
+  listing:         bool,
+  map:             bool,
+  /// Directory of a workspace member, relative to the workspace
+  directory:       Option<String>,
+  tests:           BTreeMap<String, divina_config::Test>,
+  unit_tests:      BTreeMap<String, divina_config::UnitTest>,
+  benches:         BTreeMap<String, divina_config::Bench>,
+  hooks:           divina_config::Hooks,
+  /// Files which `Package.build` reads, relative to the workspace
+  build_inputs:    Vec<String>,
+  constants:       BTreeMap<String, String>,
+  structs:         BTreeMap<String, Vec<divina_config::StructField>>,
+}
+impl Package {
+  /// `path` relative to the workspace, rather than to the package
+  fn resolve_path(&self, path: &str) -> String {
+    self.directory.as_ref().map_or_else(
+      || path.to_string(),
+      |directory| format!("{}/{}", directory, path),
+    )
+  }
+
+  /// Macros which Divina defines for every source of the package
+  ///
+  /// Macros with an empty value are only defined, for use with `%ifdef`.
+  fn predefined_macros(&self) -> BTreeMap<String, String> {
+    let mut macros = BTreeMap::new();
+
+    macros.insert("DIVINA_VERSION".to_string(), format!("\"{}\"", VERSION));
+    macros.insert(
+      "DIVINA_PACKAGE_NAME".to_string(),
+      format!("\"{}\"", self.name),
+    );
+    macros.insert(
+      "DIVINA_PACKAGE_VERSION".to_string(),
+      format!("\"{}\"", self.version),
+    );
+    macros.insert(
+      format!("DIVINA_ARCH_{}", format!("{:?}", self.arch).to_uppercase()),
+      String::new(),
+    );
+    macros.insert(
+      "DIVINA_TARGET_OS".to_string(),
+      format!("\"{}\"", std::env::consts::OS),
+    );
+    macros.insert(
+      "DIVINA_PROFILE".to_string(),
+      format!("\"{}\"", self.profile.name),
+    );
+    macros.insert(self.profile.macro_name(), String::new());
+
+    macros
+  }
+
+  /// Assembler flags for `include_dirs` (`-I`), predefined macros, `defines`
+  /// (`-D`), `compile_options` and the profile's `compile_options`
+  fn assembler_flags(&self) -> Vec<String> {
+    let mut flags = Vec::new();
+
+    for include_dir in &self.include_dirs {
+      // NASM concatenates the include path and the filename verbatim, so the
+      // trailing slash is required.
+      flags.push(if include_dir.ends_with('/') {
+        format!("-I{}", include_dir)
+      } else {
+        format!("-I{}/", include_dir)
+      });
+    }
+
+    // User defines come last so that they take precedence
+    for (name, value) in self.predefined_macros().iter().chain(&self.defines) {
+      flags.push(if value.is_empty() {
+        format!("-D{}", name)
+      } else {
+        format!("-D{}={}", name, value)
+      });
+    }
+
+    flags.extend(self.compile_options.iter().cloned());
+    flags.extend(self.profile.compile_options.iter().cloned());
+
+    flags
+  }
+
+  /// Output format which the assembler is invoked with
+  const fn object_format(&self) -> &'static str {
+    match (&self.arch, cfg!(windows)) {
+      (Arch::X86, false) => "elf32",
+      (Arch::X64, false) => "elf64",
+      (Arch::X86, true) => "win32",
+      (Arch::X64, true) => "win64",
+    }
+  }
+
+  /// How the assembler is invoked to compile `source`
+  fn assembler_invocation(&self, source: &Source, out_directory: &str) -> Invocation {
+    let mut arguments = vec!["-f".to_string(), self.object_format().to_string()];
+
+    arguments.extend(self.assembler_flags());
+
+    if self.listing {
+      arguments.push("-l".to_string());
+      arguments.push(format!("{}{}.lst", out_directory, source.filename));
+    }
+
+    arguments.push(source.path.clone());
+    arguments.push("-o".to_string());
+    arguments.push(source.object_path(out_directory));
+
+    Invocation {
+      program: self.compiler.clone(),
+      arguments,
+    }
+  }
+
+  /// How `ld` is invoked to link `objects` into `out_file`
+  #[cfg(unix)]
+  fn linker_invocation(
+    &self,
+    out_directory: &str,
+    out_file: &str,
+    objects: &[String],
+  ) -> Invocation {
+    let mut arguments = vec![
+      "-dynamic-linker".to_string(),
+      "/lib64/ld-linux-x86-64.so.2".to_string(),
+    ];
+
+    arguments.extend(self.linker_flags(out_directory));
+    arguments.push("-lc".to_string());
+    arguments.push("-o".to_string());
+    arguments.push(out_file.to_string());
+    arguments.extend(objects.iter().cloned());
+
+    Invocation {
+      program: "ld".to_string(),
+      arguments,
+    }
+  }
+
+  /// How `link` is invoked to link `objects` into `out_file`, from within a
+  /// Visual Studio developer command prompt environment
+  #[cfg(windows)]
+  fn linker_invocation(
+    &self,
+    out_directory: &str,
+    out_file: &str,
+    objects: &[String],
+  ) -> Invocation {
+    let mut arguments = vec!["/subsystem:console".to_string()];
+
+    arguments.extend(self.linker_flags(out_directory));
+    arguments.push(format!("/out:{}", out_file));
+    arguments.extend(objects.iter().cloned());
+    arguments.extend(
+      ["kernel32.lib", "msvcrt.lib", "legacy_stdio_definitions.lib"]
+        .iter()
+        .map(ToString::to_string),
+    );
+
+    Invocation {
+      program: "link".to_string(),
+      arguments,
+    }
+  }
+
+  /// How `cc` is invoked to link a unit test harness against `objects`
+  fn harness_invocation(&self, harness: &str, binary: &str, objects: &[String]) -> Invocation {
+    let mut arguments = Vec::new();
+
+    if self.arch == Arch::X86 {
+      arguments.push("-m32".to_string());
+    }
+
+    // Assembled objects are rarely position independent
+    if cfg!(unix) {
+      arguments.push("-no-pie".to_string());
+    }
+
+    arguments.push("-o".to_string());
+    arguments.push(binary.to_string());
+    arguments.push(harness.to_string());
+    arguments.extend(objects.iter().cloned());
+
+    Invocation {
+      program: "cc".to_string(),
+      arguments,
+    }
+  }
+
+  /// How `objcopy` copies `object` to `copy`, renaming its entry points so
+  /// that they don't clash with the `main` of a unit test harness
+  fn localize_invocation(object: &str, copy: &str) -> Invocation {
+    Invocation {
+      program:   "objcopy".to_string(),
+      arguments: vec![
+        "--redefine-sym".to_string(),
+        "main=__divina_main".to_string(),
+        "--redefine-sym".to_string(),
+        "_start=__divina_start".to_string(),
+        object.to_string(),
+        copy.to_string(),
+      ],
+    }
+  }
+
+  /// Linker flags for the profile's `link_options`, `strip` and `map`
+  fn linker_flags(&self, out_directory: &str) -> Vec<String> {
+    let mut flags = self.profile.link_options.clone();
+
+    if self.profile.strip && cfg!(unix) {
+      flags.push("-s".to_string());
+    }
+
+    if self.map {
+      flags.push(if cfg!(windows) {
+        format!("/MAP:{}{}.map", out_directory, self.name)
+      } else {
+        format!("-Map={}{}.map", out_directory, self.name)
+      });
+    }
+
+    flags
+  }
+}
+
+/// A binary produced by `Compiler::link`
+#[derive(Debug, Clone)]
+pub struct Artifact {
+  pub package:      String,
+  pub package_type: PackageType,
+  pub path:         String,
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct Compiler {
-  sources:    Vec<Package>,
-  is_package: bool,
+  sources:        Vec<Package>,
+  is_package:     bool,
+  profile:        Option<String>,
+  listing:        bool,
+  map:            bool,
+  summaries:      Vec<Summary>,
+  message_format: MessageFormat,
 }
 impl Compiler {
   #[must_use]
   pub fn new() -> Self { Self::default() }
 
-  pub fn find_sources(&mut self, config: divina_config::Config) -> &Self {
+  /// Select the build profile, `debug` is used if no profile is selected
+  pub fn set_profile(&mut self, profile: &str) -> &mut Self {
+    self.profile = Some(profile.to_string());
+    self
+  }
+
+  /// Write an assembler listing file for every source, regardless of
+  /// `Package.listing`
+  pub fn enable_listing(&mut self) -> &mut Self {
+    self.listing = true;
+    self
+  }
+
+  /// Write a linker map file for every package, regardless of `Package.map`
+  pub fn enable_map(&mut self) -> &mut Self {
+    self.map = true;
+    self
+  }
+
+  /// Select how build progress is reported
+  pub fn set_message_format(&mut self, message_format: MessageFormat) -> &mut Self {
+    self.message_format = message_format;
+    self
+  }
+
+  /// Print a status line, unless build progress is reported as JSON
+  fn notice(&self, message: &str) {
+    if self.message_format == MessageFormat::Human {
+      divina_util::output::status(message);
+    }
+  }
+
+  /// Print the command which `invocation` runs, if `--verbose` is passed and
+  /// build progress isn't reported as JSON
+  fn detail(&self, invocation: &Invocation) {
+    if self.message_format == MessageFormat::Human {
+      divina_util::output::verbose(&format!("   `{}`", invocation.command_line()));
+    }
+  }
+
+  /// Print `event`, if build progress is reported as JSON
+  fn emit(&self, event: &Event<'_>) {
+    if self.message_format == MessageFormat::Json {
+      println!(
+        "{}",
+        serde_json::to_string(event)
+          .expect("!! could not serialize build event, this *shouldn't* be possible")
+      );
+    }
+  }
+
+  /// Directory which `Package.build` generates sources into
+  fn generated_directory(&self, package: &Package) -> String {
+    format!("{}generated/", self.out_directory(package))
+  }
+
+  /// Render the `Package.constants` and `Package.structs` of every package
+  /// which defines any into `constants.inc` in its generated directory,
+  /// leaving it untouched if they haven't changed
+  ///
+  /// # Panics
+  /// if caller has insufficient permissions to write to `out/`
+  fn write_constants(&self) {
+    for package in &self.sources {
+      if package.constants.is_empty() && package.structs.is_empty() {
+        continue;
+      }
+
+      let directory = self.generated_directory(package);
+      let path = format!("{}{}", directory, constants::FILE_NAME);
+      let rendered = constants::render(
+        &package.name,
+        constants::Syntax::for_compiler(&package.compiler),
+        &package.constants,
+        &package.structs,
+      );
+
+      // Rewriting it would needlessly make every source which includes it stale
+      if fs::read_to_string(&path).ok().as_deref() == Some(rendered.as_str()) {
+        continue;
+      }
+
+      self.notice(&format!(
+        ":: {} @@ writing constants '{}'",
+        package.name, path
+      ));
+      fs::create_dir_all(&directory).unwrap_or_else(|_| {
+        panic!(
+          "!! could not create directory '{}', check permissions",
+          directory
+        )
+      });
+      fs::write(&path, rendered)
+        .unwrap_or_else(|_| panic!("!! could not write '{}', check permissions", path));
+    }
+  }
+
+  /// Run the `Package.build` of every package which defines one, unless
+  /// neither `Divina.lua` nor any of its inputs changed since it last ran, then
+  /// add the sources which it generated to the package
+  ///
+  /// # Panics
+  /// if caller has insufficient permissions to write to `out/`
+  fn generate_sources(&mut self) {
+    for package in &self.sources {
+      if !package.hooks.contains("build") {
+        continue;
+      }
+
+      let directory = self.generated_directory(package);
+      // Written once the build succeeds, so that a failed build reruns
+      let stamp = format!("{}.stamp", directory);
+      let build_script = package.resolve_path("build.lua");
+      let mut inputs = vec![package.resolve_path("Divina.lua")];
+
+      if std::path::Path::new(&build_script).exists() {
+        inputs.push(build_script);
+      }
+
+      inputs.extend(package.build_inputs.iter().cloned());
+
+      if plan::is_stale(&stamp, &inputs) {
+        fs::create_dir_all(&directory).unwrap_or_else(|_| {
+          panic!(
+            "!! could not create directory '{}', check permissions",
+            directory
+          )
+        });
+
+        if !self.hook(package, "build") {
+          divina_util::exit_with!(1, "!! {} @@ could not generate sources", package.name);
+        }
+
+        fs::write(&stamp, "")
+          .unwrap_or_else(|_| panic!("!! could not write '{}', check permissions", stamp));
+      } else {
+        self.notice(&format!(
+          ":: {} @@ generated sources are up to date",
+          package.name
+        ));
+      }
+    }
+
+    // Sources which didn't exist when they were last collected
+    self.collect_generated_sources();
+  }
+
+  /// Add the sources which `Package.build` generated, when it last ran, to
+  /// every package which defines one
+  ///
+  /// Exits if a generated source would be compiled into the same object as
+  /// another source of the package.
+  fn collect_generated_sources(&mut self) {
+    let generated = self
+      .sources
+      .iter()
+      .map(|package| {
+        if !package.hooks.contains("build") {
+          return Vec::new();
+        }
+
+        let mut sources = fs::read_dir(self.generated_directory(package))
+          .into_iter()
+          .flatten()
+          .flatten()
+          .map(|entry| entry.path())
+          .filter(|path| {
+            path
+              .extension()
+              .map_or(false, |extension| extension == "asm")
+          })
+          .map(|path| {
+            Source {
+              filename: path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default(),
+              path:     path.to_string_lossy().to_string(),
+            }
+          })
+          .collect::<Vec<_>>();
+
+        sources.sort_by(|a, b| a.path.cmp(&b.path));
+        log::debug!(
+          "{} @@ generated sources: {:?}",
+          package.name,
+          sources
+            .iter()
+            .map(|source| source.path.as_str())
+            .collect::<Vec<_>>()
+        );
+
+        sources
+      })
+      .collect::<Vec<_>>();
+
+    for (package, sources) in self.sources.iter_mut().zip(generated) {
+      for source in sources {
+        if package
+          .sources
+          .iter()
+          .any(|existing| existing.path == source.path)
+        {
+          continue;
+        }
+
+        // Objects are named after the file stem alone
+        if let Some(existing) = package
+          .sources
+          .iter()
+          .find(|existing| existing.filename == source.filename)
+        {
+          divina_util::exit_with!(
+            1,
+            "!! {} @@ generated source '{}' would be compiled into the same object as '{}', \
+             perhaps you've forgotten to rename one of them ?",
+            package.name,
+            source.path,
+            existing.path
+          );
+        }
+
+        package.sources.push(source);
+      }
+    }
+  }
+
+  /// Call the hook `name` of `package`, if it defines one, returning whether
+  /// it succeeded
+  fn hook(&self, package: &Package, name: &str) -> bool {
+    if !package.hooks.contains(name) {
+      return true;
+    }
+
+    // `Package.build` is called like a hook, but isn't one of `Package.hooks`
+    let function = if name == "build" {
+      "`Package.build`".to_string()
+    } else {
+      format!("hook `Package.hooks.{}`", name)
+    };
+
+    self.notice(&format!(":: {} @@ running {}", package.name, function));
+
+    let context = BTreeMap::from([
+      ("package", package.name.clone()),
+      ("version", package.version.clone()),
+      ("profile", package.profile.name.clone()),
+      (
+        "directory",
+        package.directory.clone().unwrap_or_else(|| ".".to_string()),
+      ),
+      ("out_dir", self.out_directory(package)),
+      ("generated_dir", self.generated_directory(package)),
+      ("artifact", self.binary_path(package)),
+    ]);
+
+    package
+      .hooks
+      .call(name, &context)
+      .map_err(|error| {
+        divina_util::output::error(&format!(
+          "!! {} @@ {} failed: {}",
+          package.name, function, error
+        ));
+      })
+      .is_ok()
+  }
+
+  /// Resolve the selected build profile for a package
+  fn resolve_profile(
+    &self,
+    package_name: &str,
+    compiler: &str,
+    profiles: Option<&BTreeMap<String, divina_config::Profile>>,
+  ) -> Profile {
+    let name = self.profile.as_deref().unwrap_or(profile::DEFAULT_PROFILE);
+
+    Profile::resolve(name, compiler, profiles).unwrap_or_else(|| {
+      divina_util::exit_with!(
+        1,
+        "!! {} @@ profile '{}' is not defined, perhaps you've forgotten to add it to \
+         `Package.profiles` ?",
+        package_name,
+        name
+      );
+    })
+  }
+
+  /// Directory which a package's objects and binary are placed into
+  fn out_directory(&self, package: &Package) -> String {
+    if self.is_package {
+      format!("out/{}/", package.profile.name)
+    } else {
+      format!("out/{}/{}/", package.profile.name, package.name)
+    }
+  }
+
+  /// Path of the binary which `link` produces for a package
+  fn binary_path(&self, package: &Package) -> String {
+    format!(
+      "{}{}{}",
+      self.out_directory(package),
+      package.name,
+      if cfg!(windows) { ".exe" } else { "" }
+    )
+  }
+
+  /// The binaries which `link` produces
+  #[must_use]
+  pub fn artifacts(&self) -> Vec<Artifact> {
+    self
+      .sources
+      .iter()
+      .map(|package| {
+        Artifact {
+          package:      package.name.clone(),
+          package_type: package.package_type.clone(),
+          path:         self.binary_path(package),
+        }
+      })
+      .collect()
+  }
+
+  #[allow(clippy::too_many_lines)]
+  pub fn find_sources(&mut self, config: divina_config::Config) -> &mut Self {
     if config.config_type == divina_config::ConfigType::Workspace {
       for member in config.members.expect(
         "!! could not access 'Config.members' from `workspace`, this *shouldn't* be possible",
       ) {
+        let name = member.name.expect(
+          "!! could not access `Config.?.name` from `workspace`, this *shouldn't* be possible",
+        );
+        let compiler = member.compiler.unwrap_or_else(|| "yasm".to_string());
+        let profile = self.resolve_profile(&name, &compiler, member.profiles.as_ref());
         let mut package = Package {
-          name:          member.name.expect(
-            "!! could not access `Config.?.name` from `workspace`, this *shouldn't* be possible",
+          name,
+          version: member.version.expect(
+            "!! could not access `Config.?.version` from `workspace`, this *shouldn't* be possible",
           ),
-          sources:       Vec::new(),
-          arch:          member
+          sources: Vec::new(),
+          package_type: member.package_type.unwrap_or(PackageType::Bin),
+          arch: member
             .arch
             .expect("!! could not access 'Config.members.?.arch', this *shouldn't* be possible"),
-          compiler:      member.compiler.unwrap_or_else(|| "yasm".to_string()),
+          compiler,
           visual_studio: member.visual_studio,
+          include_dirs: member
+            .include_dirs
+            .unwrap_or_default()
+            .iter()
+            .map(|include_dir| {
+              format!(
+                "{}/{}",
+                member.path.as_ref().expect(
+                  "!! could not access 'Config.members.?.path', this *shouldn't* be possible"
+                ),
+                include_dir
+              )
+            })
+            .collect(),
+          defines: member.defines.unwrap_or_default(),
+          compile_options: member.compile_options.unwrap_or_default(),
+          profile,
+          listing: self.listing || member.listing.unwrap_or(false),
+          map: self.map || member.map.unwrap_or(false),
+          directory: member.path.clone(),
+          tests: member.tests.unwrap_or_default(),
+          unit_tests: member.unit_tests.unwrap_or_default(),
+          benches: member.benches.unwrap_or_default(),
+          hooks: member.hooks,
+          build_inputs: member
+            .build_inputs
+            .unwrap_or_default()
+            .iter()
+            .map(|build_input| {
+              format!(
+                "{}/{}",
+                member.path.as_ref().expect(
+                  "!! could not access 'Config.members.?.path', this *shouldn't* be possible"
+                ),
+                build_input
+              )
+            })
+            .collect(),
+          constants: member.constants.unwrap_or_default(),
+          structs: member.structs.unwrap_or_default(),
         };
 
         member
@@ -95,22 +744,43 @@ impl Compiler {
         self.sources.push(package);
       }
     } else {
+      let name = config
+        .name
+        .expect("!! could not access `Config.name` from `Package`, this *shouldn't* be possible");
+      let compiler = if config.compiler.is_some() {
+        config
+          .compiler
+          .expect("!! could not access 'Config.compiler', this *shouldn't be possible")
+      } else {
+        "yasm".to_string()
+      };
+      let profile = self.resolve_profile(&name, &compiler, config.profiles.as_ref());
       let mut package = Package {
-        name:          config
-          .name
-          .expect("!! could not access `Config.name` from `Package`, this *shouldn't* be possible"),
-        sources:       Vec::new(),
-        arch:          config
+        name,
+        version: config.version.expect(
+          "!! could not access `Config.version` from `Package`, this *shouldn't* be possible",
+        ),
+        sources: Vec::new(),
+        package_type: config.package_type.unwrap_or(PackageType::Bin),
+        arch: config
           .arch
           .expect("!! could not access 'Config.arch', this *shouldn't* be possible"),
-        compiler:      if config.compiler.is_some() {
-          config
-            .compiler
-            .expect("!! could not access 'Config.compiler', this *shouldn't be possible")
-        } else {
-          "yasm".to_string()
-        },
+        compiler,
         visual_studio: config.visual_studio,
+        include_dirs: config.include_dirs.unwrap_or_default(),
+        defines: config.defines.unwrap_or_default(),
+        compile_options: config.compile_options.unwrap_or_default(),
+        profile,
+        listing: self.listing || config.listing.unwrap_or(false),
+        map: self.map || config.map.unwrap_or(false),
+        directory: None,
+        tests: config.tests.unwrap_or_default(),
+        unit_tests: config.unit_tests.unwrap_or_default(),
+        benches: config.benches.unwrap_or_default(),
+        hooks: config.hooks,
+        build_inputs: config.build_inputs.unwrap_or_default(),
+        constants: config.constants.unwrap_or_default(),
+        structs: config.structs.unwrap_or_default(),
       };
 
       config
@@ -144,30 +814,81 @@ impl Compiler {
 
     self.is_package = self.sources.len() == 1;
 
+    // Sources which `Package.build` generates and `constants.inc` may be
+    // included from the generated directory
+    let generated = self
+      .sources
+      .iter()
+      .map(|package| {
+        (package.hooks.contains("build")
+          || !package.constants.is_empty()
+          || !package.structs.is_empty())
+        .then(|| self.generated_directory(package))
+      })
+      .collect::<Vec<_>>();
+
+    for (package, directory) in self.sources.iter_mut().zip(generated) {
+      if let Some(directory) = directory {
+        package.include_dirs.push(directory);
+      }
+    }
+
+    // So that plans, `compile_commands.json` and generated build files list
+    // them too, `compile` collects them again once `Package.build` reruns
+    self.collect_generated_sources();
+
+    for package in &self.sources {
+      log::debug!(
+        "{} @@ resolved profile '{}' and out directory '{}'",
+        package.name,
+        package.profile.name,
+        self.out_directory(package)
+      );
+
+      for source in &package.sources {
+        log::debug!(
+          "{} @@ resolved source '{}' to object '{}'",
+          package.name,
+          source.path,
+          source.object_path(&self.out_directory(package))
+        );
+      }
+    }
+
     self
   }
 
+  /// Compile every source, printing the diagnostics of each package
+  ///
   /// # Panics
   /// if caller has insufficient permissions to create a directory
-  #[must_use]
-  pub fn compile(&self) -> &Self {
+  pub fn compile(&mut self) -> &mut Self {
+    let color = divina_util::output::color(divina_util::output::Stream::Stderr);
+    let mut summaries = Vec::new();
+
     if !std::path::Path::new("out/").exists() {
-      println!(":: creating directory 'out/'");
+      self.notice(":: creating directory 'out/'");
       fs::create_dir_all("out/").expect("!! could not create directory 'out/', check permissions");
     }
 
+    self.generate_sources();
+    self.write_constants();
+
     for package in &self.sources {
-      let package_out_directory = if self.is_package {
-        "out/".to_string()
-      } else {
-        format!("out/{}/", package.name)
-      };
+      let package_out_directory = self.out_directory(package);
+      let mut output = String::new();
+      let mut failed = false;
+
+      self.emit(&Event::PackageStarted {
+        package: &package.name,
+        profile: &package.profile.name,
+      });
 
       if !std::path::Path::new(&package_out_directory).exists() {
-        println!(
+        self.notice(&format!(
           ":: {} @@ creating directory '{}'",
           package.name, package_out_directory
-        );
+        ));
         fs::create_dir_all(&package_out_directory).unwrap_or_else(|_| {
           panic!(
             "!! could not create directory '{}', check permissions",
@@ -176,157 +897,642 @@ impl Compiler {
         });
       }
 
-      for source in &package.sources {
-        println!(
+      // Sources may depend on what a failed `pre_build` hook didn't generate
+      let prepared = self.hook(package, "pre_build");
+
+      failed |= !prepared;
+
+      for source in package.sources.iter().filter(|_| prepared) {
+        self.notice(&format!(
           ":: {} @@ {} ?? compiling source '{}'",
           package.name, package.compiler, source.path
-        );
+        ));
 
-        #[cfg(unix)]
-        unix::compile(
-          &package.compiler,
-          if package.arch == Arch::X86 {
-            "elf32"
-          } else {
-            "elf64"
-          },
-          &source.path,
-          &if self.is_package {
-            format!("out/{}.o", source.filename)
-          } else {
-            format!("out/{}/{}.o", package.name, source.filename)
-          },
+        let started = Instant::now();
+        let invocation = package.assembler_invocation(source, &package_out_directory);
+
+        self.detail(&invocation);
+
+        let result = invocation.output().unwrap_or_else(|_| {
+          divina_util::exit_with!(
+            1,
+            "!! {} @@ could not run '{}', perhaps it isn't installed ?",
+            package.name,
+            package.compiler
+          );
+        });
+        let source_output = format!(
+          "{}{}",
+          String::from_utf8_lossy(&result.stdout),
+          String::from_utf8_lossy(&result.stderr)
         );
 
-        #[cfg(windows)]
-        windows::compile(
-          &package.compiler,
-          if package.arch == Arch::X86 {
-            "win32"
-          } else {
-            "win64"
-          },
-          &source.path,
-          &if self.is_package {
-            format!("out/{}.obj", source.filename)
-          } else {
-            format!("out/{}/{}.obj", package.name, source.filename)
-          },
+        self.emit(&Event::SourceCompiled {
+          package:     &package.name,
+          source:      &source.path,
+          object:      &source.object_path(&package_out_directory),
+          success:     result.status.success(),
+          duration:    started.elapsed().as_secs_f64(),
+          diagnostics: &Diagnostic::parse_all(&source_output),
+        });
+
+        output.push_str(&source_output);
+        failed |= !result.status.success();
+      }
+
+      if !failed {
+        failed = !self.hook(package, "post_compile");
+      }
+
+      let diagnostics = Diagnostic::parse_all(&output);
+
+      if self.message_format == MessageFormat::Human {
+        for diagnostic in &diagnostics {
+          eprintln!("{}\n", diagnostic.render(color));
+        }
+
+        // Don't swallow the output of a failure which couldn't be parsed
+        if failed && diagnostics.is_empty() {
+          eprint!("{}", output);
+        }
+      }
+
+      summaries.push(Summary::new(&package.name, &diagnostics, failed));
+    }
+
+    self.summaries = summaries;
+
+    self
+  }
+
+  /// Print the error and warning counts of every package which reported any
+  ///
+  /// Exits if any package failed to build.
+  pub fn summarize(&self) {
+    let failed = self
+      .summaries
+      .iter()
+      .filter(|summary| summary.failed)
+      .count();
+
+    for summary in &self.summaries {
+      if summary.errors > 0 || summary.warnings > 0 || summary.failed {
+        self.notice(&format!(":: {} @@ {}", summary.package, summary));
+      }
+    }
+
+    self.emit(&Event::BuildFinished {
+      success: failed == 0,
+    });
+
+    if failed > 0 {
+      if self.message_format == MessageFormat::Human {
+        divina_util::exit_with!(
+          1,
+          "!! could not build {} package{}",
+          failed,
+          if failed == 1 { "" } else { "s" }
         );
+      } else {
+        divina_util::exit_with!(1);
       }
     }
+  }
 
+  /// Whether any source of `package` failed to compile
+  fn has_failed(&self, package: &Package) -> bool {
     self
+      .summaries
+      .iter()
+      .any(|summary| summary.failed && summary.package == package.name)
   }
 
+  /// Link the objects of every package which compiled successfully
+  ///
   /// # Panics
   /// if Visual Studio 2019 is not installed
-  pub fn link(&self) {
+  pub fn link(&mut self) {
+    let mut link_failures = Vec::new();
+
     for package in &self.sources {
+      if self.has_failed(package) {
+        self.notice(&format!(
+          ":: {} @@ skipping link, compilation failed",
+          package.name
+        ));
+
+        continue;
+      }
+
+      let package_out_directory = self.out_directory(package);
+      let out_file = self.binary_path(package);
       let mut filenames = Vec::new();
       #[allow(unused)]
       let mut arch = &Arch::X86;
 
       for source in &package.sources {
-        filenames.push(format!(
-          "out/{}{}.{}",
-          &if self.is_package {
-            "".to_string()
-          } else {
-            format!("{}/", &package.name)
-          },
-          source.filename,
-          {
-            if cfg!(windows) {
-              "obj"
-            } else {
-              "o"
-            }
-          }
-        ));
+        filenames.push(source.object_path(&package_out_directory));
 
         #[allow(unused)]
         arch = &package.arch;
       }
 
       #[cfg(windows)]
-      println!(
+      self.notice(&format!(
         ":: {} @@ entering visual studio developer command prompt environment",
         package.name
-      );
+      ));
 
-      println!(
+      self.notice(&format!(
         ":: {} @@ linking source{}: '{}'",
         package.name,
         if filenames.len() > 1 { "s" } else { "" },
         filenames.join("', '")
-      );
+      ));
+
+      let started = Instant::now();
 
       #[cfg(unix)]
-      unix::link(
-        "ld",
-        "/lib64/ld-linux-x86-64.so.2",
-        &if self.is_package {
-          format!("out/{}", package.name)
-        } else {
-          format!("out/{}/{}", package.name, package.name)
-        },
-        &filenames.join(" "),
-      );
+      let (success, output) = {
+        let invocation = package.linker_invocation(&package_out_directory, &out_file, &filenames);
+
+        self.detail(&invocation);
+
+        let result = invocation.output().unwrap_or_else(|_| {
+          divina_util::exit_with!(
+            1,
+            "!! {} @@ could not run 'ld', perhaps it isn't installed ?",
+            package.name
+          );
+        });
+
+        (
+          result.status.success(),
+          format!(
+            "{}{}",
+            String::from_utf8_lossy(&result.stdout),
+            String::from_utf8_lossy(&result.stderr)
+          ),
+        )
+      };
 
       #[cfg(windows)]
-      {
-        if let Some(visual_studio_path) = &package.visual_studio {
-          windows::link_package_custom(&filenames.join(" "), &package.name, visual_studio_path);
-        } else if arch == &Arch::X64 {
-          if self.is_package {
-            windows::link_package_64(&filenames.join(" "), &package.name);
+      let (success, output) = {
+        let flags = package.linker_flags(&package_out_directory).join(" ");
+
+        log::debug!(
+          "running `{}` in a visual studio developer command prompt environment",
+          package
+            .linker_invocation(&package_out_directory, &out_file, &filenames)
+            .command_line()
+        );
+
+        (
+          true,
+          if let Some(visual_studio_path) = &package.visual_studio {
+            windows::link_custom(&flags, &filenames.join(" "), &out_file, visual_studio_path)
+          } else if arch == &Arch::X64 {
+            windows::link_64(&flags, &filenames.join(" "), &out_file)
           } else {
-            windows::link_workspace_64(&filenames.join(" "), &package.name);
-          }
-        } else if self.is_package {
-          windows::link_package_32(&filenames.join(" "), &package.name);
-        } else {
-          windows::link_workspace_32(&filenames.join(" "), &package.name);
+            windows::link_32(&flags, &filenames.join(" "), &out_file)
+          },
+        )
+      };
+
+      self.emit(&Event::PackageLinked {
+        package: &package.name,
+        success,
+        duration: started.elapsed().as_secs_f64(),
+        output: &output,
+      });
+
+      if success {
+        self.emit(&Event::Artifact {
+          package: &package.name,
+          path:    &out_file,
+        });
+
+        if !self.hook(package, "post_link") {
+          link_failures.push(package.name.clone());
+        }
+      } else {
+        if self.message_format == MessageFormat::Human {
+          eprint!("{}", output);
         }
+        self.notice(&format!(":: {} @@ could not link", package.name));
+        link_failures.push(package.name.clone());
+      }
+    }
+
+    for summary in &mut self.summaries {
+      if link_failures.contains(&summary.package) {
+        summary.failed = true;
       }
     }
   }
 
+  /// The `Package.tests` of every package, or only of `member`, whose names
+  /// contain `filter`
   #[must_use]
-  pub fn print_config(&self) -> &Self {
-    println!("{:?}", self);
+  pub fn test_cases(&self, member: Option<&str>, filter: Option<&str>) -> Vec<TestCase> {
+    if let Some(name) = member {
+      if !self.sources.iter().any(|package| package.name == name) {
+        divina_util::exit_with!(1, "!! no workspace member is named '{}'", name);
+      }
+    }
 
-    self
+    let mut cases = Vec::new();
+
+    for package in &self.sources {
+      if package.tests.is_empty() || member.map_or(false, |member| member != package.name) {
+        continue;
+      }
+
+      if package.package_type == PackageType::Lib {
+        self.notice(&format!(
+          ":: {} @@ skipping tests, `Lib` packages cannot be run",
+          package.name
+        ));
+
+        continue;
+      }
+
+      for (name, test) in &package.tests {
+        if !name.contains(filter.unwrap_or_default()) {
+          continue;
+        }
+
+        cases.push(TestCase {
+          package:      package.name.clone(),
+          name:         name.clone(),
+          binary:       self.binary_path(package),
+          args:         test.args.clone().unwrap_or_default(),
+          stdin:        test.stdin.clone(),
+          stdout:       test.stdout.clone(),
+          stderr:       test.stderr.clone(),
+          golden:       test
+            .golden
+            .as_ref()
+            .map(|golden| package.resolve_path(golden)),
+          exit_code:    test.exit_code.unwrap_or(0),
+          timeout:      test.timeout.map(std::time::Duration::from_secs_f64),
+          explains:     false,
+          cpu_limit:    test.cpu_limit,
+          memory_limit: test.memory_limit,
+          memcheck:     false,
+          unprepared:   None,
+        });
+      }
+    }
+
+    cases
   }
-}
 
-#[cfg(unix)]
-#[rustfmt::skip] // Preserve raw string literal positions
-mod unix {
-  use shellfn::shell;
+  /// The `Package.benches` of every package, or only of `member`, whose names
+  /// contain `filter`
+  #[must_use]
+  pub fn bench_cases(&self, member: Option<&str>, filter: Option<&str>) -> Vec<BenchCase> {
+    if let Some(name) = member {
+      if !self.sources.iter().any(|package| package.name == name) {
+        divina_util::exit_with!(1, "!! no workspace member is named '{}'", name);
+      }
+    }
 
-  #[shell]
-  pub fn compile(compiler: &str, architecture: &str, source_path: &str, out_file: &str) -> String { r#"
-    $COMPILER -f $ARCHITECTURE $SOURCE_PATH -o $OUT_FILE
-  "# }
+    let mut cases = Vec::new();
 
-  #[shell]
-  pub fn link(linker: &str, dynamic_linker: &str, out_file: &str, sources: &str) -> String { r#"
-    $LINKER -dynamic-linker $DYNAMIC_LINKER -lc -o $OUT_FILE $SOURCES
-  "# }
+    for package in &self.sources {
+      if package.benches.is_empty() || member.map_or(false, |member| member != package.name) {
+        continue;
+      }
+
+      if package.package_type == PackageType::Lib {
+        self.notice(&format!(
+          ":: {} @@ skipping benches, `Lib` packages cannot be run",
+          package.name
+        ));
+
+        continue;
+      }
+
+      for (name, bench) in &package.benches {
+        if !name.contains(filter.unwrap_or_default()) {
+          continue;
+        }
+
+        cases.push(BenchCase {
+          package:    package.name.clone(),
+          name:       name.clone(),
+          binary:     self.binary_path(package),
+          args:       bench.args.clone().unwrap_or_default(),
+          stdin:      bench.stdin.clone(),
+          iterations: bench.iterations.unwrap_or(100),
+          warmup:     bench.warmup.unwrap_or(3),
+        });
+      }
+    }
+
+    cases
+  }
+
+  /// Run `invocation` on behalf of `package`, returning its output if it
+  /// failed
+  fn run_harness_step(&self, package: &Package, invocation: &Invocation) -> Result<(), String> {
+    self.detail(invocation);
+
+    let result = invocation.output().map_err(|_| {
+      format!(
+        "could not run '{}', perhaps it isn't installed ?",
+        invocation.program
+      )
+    })?;
+
+    if result.status.success() {
+      Ok(())
+    } else {
+      log::debug!("{} @@ '{}' failed", package.name, invocation.program);
+
+      Err(format!(
+        "{}{}",
+        String::from_utf8_lossy(&result.stdout),
+        String::from_utf8_lossy(&result.stderr)
+      ))
+    }
+  }
+
+  /// Compile `harness` into `binary`, linked against the objects of `package`
+  ///
+  /// The entry points of a `Bin` package would clash with the harness's
+  /// `main`, so on Unix the harness is linked against copies of its objects in
+  /// which they are renamed.
+  ///
+  /// # Errors
+  /// the output of the step which failed, if any did
+  fn build_harness(&self, package: &Package, harness: &str, binary: &str) -> Result<(), String> {
+    let package_out_directory = self.out_directory(package);
+    let mut objects = Vec::new();
+
+    for source in &package.sources {
+      let object = source.object_path(&package_out_directory);
+
+      if cfg!(unix) {
+        let directory = format!("{}unit_test_objects/", package_out_directory);
+        let copy = source.object_path(&directory);
+
+        fs::create_dir_all(&directory).unwrap_or_else(|_| {
+          panic!(
+            "!! could not create directory '{}', check permissions",
+            directory
+          )
+        });
+        self.run_harness_step(package, &Package::localize_invocation(&object, &copy))?;
+        objects.push(copy);
+      } else {
+        objects.push(object);
+      }
+    }
+
+    self.notice(&format!(
+      ":: {} @@ cc ?? compiling unit test harness '{}'",
+      package.name, harness
+    ));
+    self.run_harness_step(
+      package,
+      &package.harness_invocation(harness, binary, &objects),
+    )
+  }
+
+  /// Generate and link a harness for the `Package.unit_tests` of every package,
+  /// or only of `member`, returning a test for each case whose name contains
+  /// `filter`
+  ///
+  /// A harness which can't be built is reported as a single failed test of its
+  /// package, so that the tests of other packages still run.
+  ///
+  /// # Panics
+  /// if caller has insufficient permissions to write the harness
+  #[must_use]
+  pub fn unit_test_cases(&self, member: Option<&str>, filter: Option<&str>) -> Vec<TestCase> {
+    let mut cases = Vec::new();
+
+    for package in &self.sources {
+      if package.unit_tests.is_empty() || member.map_or(false, |member| member != package.name) {
+        continue;
+      }
+
+      let package_out_directory = self.out_directory(package);
+      let harness = format!("{}unit_tests.c", package_out_directory);
+      let binary = format!(
+        "{}unit_tests{}",
+        package_out_directory,
+        if cfg!(windows) { ".exe" } else { "" }
+      );
+      let (source, names) = unit::harness(&package.name, &package.unit_tests);
+      let names = names
+        .into_iter()
+        .enumerate()
+        .filter(|(_, name)| name.contains(filter.unwrap_or_default()))
+        .collect::<Vec<_>>();
+
+      if names.is_empty() {
+        continue;
+      }
+
+      fs::write(&harness, source)
+        .unwrap_or_else(|_| panic!("!! could not write '{}', check permissions", harness));
+
+      let case = |name: String, args: Vec<String>, unprepared: Option<String>| {
+        TestCase {
+          package: package.name.clone(),
+          name,
+          binary: binary.clone(),
+          args,
+          stdin: None,
+          stdout: None,
+          stderr: None,
+          golden: None,
+          exit_code: 0,
+          timeout: None,
+          explains: true,
+          cpu_limit: None,
+          memory_limit: None,
+          memcheck: false,
+          unprepared,
+        }
+      };
+
+      if let Err(output) = self.build_harness(package, &harness, &binary) {
+        cases.push(case(
+          "unit test harness".to_string(),
+          Vec::new(),
+          Some(format!(
+            "could not compile unit test harness '{}':\n{}",
+            harness,
+            output.trim_end()
+          )),
+        ));
+
+        continue;
+      }
+
+      for (index, name) in names {
+        cases.push(case(name, vec![index.to_string()], None));
+      }
+    }
+
+    cases
+  }
+
+  /// Every command which `compile` and `link` would run, in order
+  #[must_use]
+  pub fn plan(&self) -> Vec<Step> {
+    let mut steps = Vec::new();
+
+    for package in &self.sources {
+      let package_out_directory = self.out_directory(package);
+      let out_file = self.binary_path(package);
+      let mut objects = Vec::new();
+
+      for source in &package.sources {
+        let object = source.object_path(&package_out_directory);
+
+        steps.push(Step {
+          package:    package.name.clone(),
+          kind:       StepKind::Compile,
+          invocation: package.assembler_invocation(source, &package_out_directory),
+          inputs:     vec![source.path.clone()],
+          output:     object.clone(),
+          reason:     plan::staleness(&object, std::slice::from_ref(&source.path)),
+        });
+        objects.push(object);
+      }
+
+      steps.push(Step {
+        package:    package.name.clone(),
+        kind:       StepKind::Link,
+        invocation: package.linker_invocation(&package_out_directory, &out_file, &objects),
+        reason:     format!(
+          "{}, skipped if any source of '{}' fails to compile",
+          plan::staleness(&out_file, &objects),
+          package.name
+        ),
+        inputs:     objects,
+        output:     out_file,
+      });
+    }
+
+    steps
+  }
+
+  /// Print every command which `compile` and `link` would run without running
+  /// them, along with why each would run if `explain` is set
+  pub fn dry_run(&self, explain: bool) {
+    for step in self.plan() {
+      if explain {
+        println!(
+          "# {} @@ {}: {}",
+          step.package,
+          match step.kind {
+            StepKind::Compile => "compile",
+            StepKind::Link => "link",
+          },
+          step.reason
+        );
+      }
+
+      println!("{}", step.invocation.command_line());
+    }
+  }
+
+  /// Write `compile_commands.json` for every source of every package into the
+  /// workspace root
+  ///
+  /// # Panics
+  /// if caller has insufficient permissions to write to the workspace root
+  pub fn write_compile_commands(&self) {
+    let directory = std::env::current_dir()
+      .expect("!! could not access the current directory, check permissions");
+
+    fs::write(
+      "compile_commands.json",
+      compdb::render(&directory.to_string_lossy(), &self.plan()),
+    )
+    .expect("!! could not write 'compile_commands.json', check permissions");
+  }
+
+  /// Render a standalone build file which runs the same commands as
+  /// `compile` and `link`
+  #[must_use]
+  pub fn generate(&self, generator: Generator) -> String { generator.render(&self.plan()) }
+
+  /// Render the JSON metadata of every package, as printed by
+  /// `divina metadata`
+  ///
+  /// # Panics
+  /// if caller has insufficient permissions to access the workspace root
+  #[must_use]
+  pub fn metadata(&self) -> String {
+    let directory = std::env::current_dir()
+      .expect("!! could not access the current directory, check permissions");
+    let packages = self
+      .sources
+      .iter()
+      .map(|package| {
+        metadata::Package {
+          name:          &package.name,
+          version:       &package.version,
+          package_type:  match package.package_type {
+            PackageType::Bin => "bin",
+            PackageType::Lib => "lib",
+          },
+          arch:          match package.arch {
+            Arch::X86 => "x86",
+            Arch::X64 => "x64",
+          },
+          compiler:      &package.compiler,
+          profile:       &package.profile.name,
+          directory:     package.directory.as_deref(),
+          sources:       package
+            .sources
+            .iter()
+            .map(|source| source.path.as_str())
+            .collect(),
+          out_directory: self.out_directory(package),
+          artifact:      self.binary_path(package),
+          tests:         package.tests.keys().map(String::as_str).collect(),
+          unit_tests:    package.unit_tests.keys().map(String::as_str).collect(),
+          benches:       package.benches.keys().map(String::as_str).collect(),
+        }
+      })
+      .collect::<Vec<_>>();
+
+    metadata::render(&directory.to_string_lossy(), &packages)
+  }
+
+  #[must_use]
+  pub fn print_config(&self) -> &Self {
+    println!("{:?}", self);
+
+    for package in &self.sources {
+      println!(":: {} @@ predefined macros", package.name);
+
+      for (name, value) in package.predefined_macros() {
+        if value.is_empty() {
+          println!("   {}", name);
+        } else {
+          println!("   {} = {}", name, value);
+        }
+      }
+    }
+
+    self
+  }
 }
 
 #[cfg(windows)]
-#[rustfmt::skip]
+#[rustfmt::skip] // Preserve raw string literal positions
 mod windows {
   use shellfn::shell;
 
-  #[shell(cmd = "powershell")]
-  pub fn compile(compiler: &str, architecture: &str, sources: &str, out_file: &str) -> String { r#"
-    $COMPILER -f $ARCHITECTURE $SOURCES -o $OUT_FILE
-  "# }
-
   /// Thank lord for the [shellfn](https://github.com/synek317/shellfn) crate...
   ///
   /// I unironically spent **SIX** hours -- give or take a few minutes... --
@@ -336,23 +1542,15 @@ mod windows {
   ///
   /// Thanks, shellfn.
   #[shell(cmd = "powershell")]
-  pub fn link_workspace_32(objects: &str, filename: &str) -> String { r#"
-    "link /subsystem:console /out:out/$FILENAME/$FILENAME.exe $OBJECTS kernel32.lib msvcrt.lib legacy_stdio_definitions.lib" | cmd /k "C:\Program Files\Microsoft Visual Studio\2022\Community\VC\Auxiliary\Build\vcvars32.bat"
-  "# }
-  #[shell(cmd = "powershell")]
-  pub fn link_workspace_64(objects: &str, filename: &str) -> String { r#"
-    "link /subsystem:console /out:out/$FILENAME/$FILENAME.exe $OBJECTS kernel32.lib msvcrt.lib legacy_stdio_definitions.lib" | cmd /k "C:\Program Files\Microsoft Visual Studio\2022\Community\VC\Auxiliary\Build\vcvars64.bat"
-  "# }
-  #[shell(cmd = "powershell")]
-  pub fn link_package_32(objects: &str, filename: &str) -> String { r#"
-    "link /subsystem:console /out:out/$FILENAME.exe $OBJECTS kernel32.lib msvcrt.lib legacy_stdio_definitions.lib" | cmd /k "C:\Program Files\Microsoft Visual Studio\2022\Community\VC\Auxiliary\Build\vcvars32.bat"
+  pub fn link_32(flags: &str, objects: &str, out_file: &str) -> String { r#"
+    "link /subsystem:console $FLAGS /out:$OUT_FILE $OBJECTS kernel32.lib msvcrt.lib legacy_stdio_definitions.lib" | cmd /k "C:\Program Files\Microsoft Visual Studio\2022\Community\VC\Auxiliary\Build\vcvars32.bat"
   "# }
   #[shell(cmd = "powershell")]
-  pub fn link_package_64(objects: &str, filename: &str) -> String { r#"
-    "link /subsystem:console /out:out/$FILENAME.exe $OBJECTS kernel32.lib msvcrt.lib legacy_stdio_definitions.lib" | cmd /k "C:\Program Files\Microsoft Visual Studio\2022\Community\VC\Auxiliary\Build\vcvars64.bat"
+  pub fn link_64(flags: &str, objects: &str, out_file: &str) -> String { r#"
+    "link /subsystem:console $FLAGS /out:$OUT_FILE $OBJECTS kernel32.lib msvcrt.lib legacy_stdio_definitions.lib" | cmd /k "C:\Program Files\Microsoft Visual Studio\2022\Community\VC\Auxiliary\Build\vcvars64.bat"
   "# }
   #[shell(cmd = "powershell")]
-  pub fn link_package_custom(objects: &str, filename: &str, visual_studio_path: &str) -> String { r#"
-    "link /subsystem:console /out:out/$FILENAME.exe $OBJECTS kernel32.lib msvcrt.lib legacy_stdio_definitions.lib" | cmd /k "$VISUAL_STUDIO_PATH"
+  pub fn link_custom(flags: &str, objects: &str, out_file: &str, visual_studio_path: &str) -> String { r#"
+    "link /subsystem:console $FLAGS /out:$OUT_FILE $OBJECTS kernel32.lib msvcrt.lib legacy_stdio_definitions.lib" | cmd /k "$VISUAL_STUDIO_PATH"
   "# }
 }
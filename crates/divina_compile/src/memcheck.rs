@@ -0,0 +1,114 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Running binaries under Valgrind's memcheck and with resource limits
+
+use crate::plan::Invocation;
+
+/// A binary run under memcheck, which reports into a log file so that the
+/// binary's own output is left untouched
+#[derive(Debug, Clone)]
+pub struct Memcheck {
+  pub log: String,
+}
+impl Memcheck {
+  #[must_use]
+  pub fn new(binary: &str) -> Self {
+    Self {
+      log: format!("{}.memcheck.log", binary),
+    }
+  }
+
+  /// Whether Valgrind can be run
+  #[must_use]
+  pub fn installed() -> bool {
+    Invocation {
+      program:   "valgrind".to_string(),
+      arguments: vec!["--version".to_string()],
+    }
+    .output()
+    .is_ok()
+  }
+
+  /// Wrap `invocation` in memcheck
+  #[must_use]
+  pub fn wrap(&self, invocation: Invocation) -> Invocation {
+    let mut arguments = vec![
+      "--tool=memcheck".to_string(),
+      "--leak-check=full".to_string(),
+      "--errors-for-leak-kinds=definite".to_string(),
+      format!("--log-file={}", self.log),
+      invocation.program,
+    ];
+
+    arguments.extend(invocation.arguments);
+
+    Invocation {
+      program: "valgrind".to_string(),
+      arguments,
+    }
+  }
+
+  /// memcheck's report, if it found any errors
+  #[must_use]
+  pub fn errors(&self) -> Option<String> {
+    let report = std::fs::read_to_string(&self.log).ok()?;
+    let errors = report
+      .lines()
+      .find_map(|line| line.split("ERROR SUMMARY: ").nth(1))
+      .and_then(|summary| summary.split_whitespace().next())
+      .and_then(|errors| errors.parse::<usize>().ok())
+      .unwrap_or(0);
+
+    if errors > 0 {
+      Some(report)
+    } else {
+      None
+    }
+  }
+}
+
+/// Wrap `invocation` in a shell which applies a CPU time limit in seconds and
+/// an address space limit in MiB before replacing itself with the binary
+///
+/// The limits are the shell's `ulimit`s rather than `setrlimit` calls, as
+/// those would have to be made between `fork` and `exec` by an `unsafe`
+/// `pre_exec` hook, so `sh` is required to apply them. The binary and its
+/// arguments are passed to the shell as positional parameters, which it
+/// doesn't expand or split, rather than as part of its script.
+///
+/// Limits are only enforced on Unix-like systems, other systems run
+/// `invocation` as is.
+#[must_use]
+pub fn limit(
+  invocation: Invocation,
+  cpu_limit: Option<u64>,
+  memory_limit: Option<u64>,
+) -> Invocation {
+  let mut limits = Vec::new();
+
+  if let Some(seconds) = cpu_limit {
+    limits.push(format!("ulimit -t {}", seconds));
+  }
+
+  if let Some(mebibytes) = memory_limit {
+    limits.push(format!("ulimit -v {}", mebibytes * 1024));
+  }
+
+  if limits.is_empty() || !cfg!(unix) {
+    return invocation;
+  }
+
+  let mut arguments = vec![
+    "-c".to_string(),
+    format!("{} && exec \"$0\" \"$@\"", limits.join(" && ")),
+    invocation.program,
+  ];
+
+  arguments.extend(invocation.arguments);
+
+  Invocation {
+    program: "sh".to_string(),
+    arguments,
+  }
+}
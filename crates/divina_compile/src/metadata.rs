@@ -0,0 +1,48 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! A JSON description of the workspace, as printed by `divina metadata` for
+//! plugins and other tooling
+
+use serde::Serialize;
+
+/// Bumped whenever a field is removed or changes meaning
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct Package<'a> {
+  pub name:          &'a str,
+  pub version:       &'a str,
+  #[serde(rename = "type")]
+  pub package_type:  &'static str,
+  pub arch:          &'static str,
+  pub compiler:      &'a str,
+  pub profile:       &'a str,
+  /// Directory of a workspace member, relative to the workspace
+  pub directory:     Option<&'a str>,
+  pub sources:       Vec<&'a str>,
+  pub out_directory: String,
+  /// Binary or library which `divina build` produces
+  pub artifact:      String,
+  pub tests:         Vec<&'a str>,
+  pub unit_tests:    Vec<&'a str>,
+  pub benches:       Vec<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct Metadata<'a> {
+  format_version: u32,
+  workspace_root: &'a str,
+  packages:       &'a [Package<'a>],
+}
+
+/// Render the metadata of `packages`, which belong to the workspace at
+/// `workspace_root`
+pub fn render(workspace_root: &str, packages: &[Package<'_>]) -> String {
+  serde_json::to_string_pretty(&Metadata {
+    format_version: FORMAT_VERSION,
+    workspace_root,
+    packages,
+  })
+  .expect("!! could not serialize metadata, this *shouldn't* be possible")
+}
@@ -0,0 +1,190 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! The commands which a build runs, resolved without running them
+
+use std::{
+  fs,
+  process::{Command, ExitStatus, Output},
+  time::Instant,
+};
+
+/// A program and the arguments which it is invoked with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+  pub program:   String,
+  pub arguments: Vec<String>,
+}
+impl Invocation {
+  /// The invocation as a single, shell-escaped, command line
+  #[must_use]
+  pub fn command_line(&self) -> String {
+    std::iter::once(&self.program)
+      .chain(self.arguments.iter())
+      .map(|argument| shell_escape(argument))
+      .collect::<Vec<_>>()
+      .join(" ")
+  }
+
+  /// A `Command` which runs the invocation, logging it along with the names
+  /// of the environment variables which it inherits
+  ///
+  /// Their values aren't logged, as they include whatever `dotenv` loaded
+  /// from `.env`, secrets included.
+  #[must_use]
+  pub fn command(&self) -> Command {
+    log::debug!(
+      "running `{}` in '{}'",
+      self.command_line(),
+      std::env::current_dir().unwrap_or_default().display()
+    );
+    log::trace!(
+      "environment of `{}`: {:?}",
+      self.program,
+      std::env::vars_os()
+        .map(|(name, _)| name.to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+    );
+
+    let mut command = Command::new(&self.program);
+
+    command.args(&self.arguments);
+
+    command
+  }
+
+  /// Run the invocation to completion, capturing its output
+  ///
+  /// # Errors
+  /// if the program could not be run
+  pub fn output(&self) -> std::io::Result<Output> {
+    let started = Instant::now();
+    let output = self.command().output()?;
+
+    log::debug!(
+      "`{}` exited with {} after {:.3}s",
+      self.program,
+      output.status,
+      started.elapsed().as_secs_f64()
+    );
+
+    Ok(output)
+  }
+
+  /// Run the invocation to completion, letting it inherit Divina's standard
+  /// streams
+  ///
+  /// # Errors
+  /// if the program could not be run
+  pub fn status(&self) -> std::io::Result<ExitStatus> {
+    let started = Instant::now();
+    let status = self.command().status()?;
+
+    log::debug!(
+      "`{}` exited with {} after {:.3}s",
+      self.program,
+      status,
+      started.elapsed().as_secs_f64()
+    );
+
+    Ok(status)
+  }
+}
+
+/// Quote `argument` for a POSIX shell, if it contains anything besides
+/// characters which a shell leaves alone
+fn shell_escape(argument: &str) -> String {
+  if !argument.is_empty()
+    && argument
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || "_-./=:,+@%".contains(c))
+  {
+    argument.to_string()
+  } else {
+    format!("'{}'", argument.replace('\'', "'\\''"))
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+  Compile,
+  Link,
+}
+
+/// A single command of a build
+#[derive(Debug, Clone)]
+pub struct Step {
+  pub package:    String,
+  pub kind:       StepKind,
+  pub invocation: Invocation,
+  /// Files which the step reads
+  pub inputs:     Vec<String>,
+  /// File which the step produces
+  pub output:     String,
+  /// Why the step runs, or why it may be skipped
+  pub reason:     String,
+}
+
+fn modified(path: &str) -> Option<std::time::SystemTime> {
+  fs::metadata(path)
+    .and_then(|metadata| metadata.modified())
+    .ok()
+}
+
+/// Whether `output` doesn't exist or any of `inputs` is newer than it
+pub fn is_stale(output: &str, inputs: &[String]) -> bool {
+  modified(output).map_or(true, |output_modified| {
+    inputs
+      .iter()
+      .any(|input| modified(input).map_or(false, |modified| modified > output_modified))
+  })
+}
+
+/// Describe how up to date `output` is with respect to `inputs`
+pub fn staleness(output: &str, inputs: &[String]) -> String {
+  let output_modified = match modified(output) {
+    Some(output_modified) => output_modified,
+    None => return format!("'{}' does not exist", output),
+  };
+
+  inputs
+    .iter()
+    .find(|input| modified(input).map_or(false, |modified| modified > output_modified))
+    .map_or_else(
+      || {
+        format!(
+          "'{}' is up to date, but Divina does not skip up to date steps",
+          output
+        )
+      },
+      |input| format!("'{}' is newer than '{}'", input, output),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::shell_escape;
+
+  #[test]
+  fn shell_escape_leaves_plain_arguments_alone() {
+    assert_eq!(shell_escape("-felf64"), "-felf64");
+    assert_eq!(shell_escape("out/debug/Main.o"), "out/debug/Main.o");
+    assert_eq!(shell_escape("-DDIVINA_ARCH_X64"), "-DDIVINA_ARCH_X64");
+  }
+
+  #[test]
+  fn shell_escape_quotes_everything_else() {
+    assert_eq!(shell_escape(""), "''");
+    assert_eq!(shell_escape("with space"), "'with space'");
+    assert_eq!(
+      shell_escape("-DDIVINA_PACKAGE_NAME=\"greet\""),
+      "'-DDIVINA_PACKAGE_NAME=\"greet\"'"
+    );
+    assert_eq!(shell_escape("$HOME"), "'$HOME'");
+  }
+
+  #[test]
+  fn shell_escape_escapes_single_quotes() {
+    assert_eq!(shell_escape("it's"), "'it'\\''s'");
+  }
+}
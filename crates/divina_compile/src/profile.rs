@@ -0,0 +1,130 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+use std::collections::BTreeMap;
+
+pub const DEFAULT_PROFILE: &str = "debug";
+
+/// A build profile, resolved from Divina's built-in defaults and a package's
+/// `profiles`
+#[derive(Debug, Clone)]
+pub struct Profile {
+  pub name:            String,
+  pub compile_options: Vec<String>,
+  pub link_options:    Vec<String>,
+  pub strip:           bool,
+}
+impl Profile {
+  /// Divina's built-in `debug` and `release` profiles for `compiler`
+  fn builtin(name: &str, compiler: &str) -> Option<Self> {
+    let compile_options: &[&str] = match (name, compiler) {
+      ("debug", "nasm") =>
+        if cfg!(windows) {
+          &["-g", "-F", "cv8"]
+        } else {
+          &["-g", "-F", "dwarf"]
+        },
+      ("debug", "yasm") =>
+        if cfg!(windows) {
+          &["-g", "cv8"]
+        } else {
+          &["-g", "dwarf2"]
+        },
+      ("release", "nasm") => &["-Ox"],
+      ("debug" | "release", _) => &[],
+      _ => return None,
+    };
+
+    Some(Self {
+      name:            name.to_string(),
+      compile_options: compile_options.iter().map(ToString::to_string).collect(),
+      // Without `/DEBUG`, the MSVC linker discards the CodeView information
+      link_options:    if name == "debug" && cfg!(windows) {
+        vec!["/DEBUG".to_string()]
+      } else {
+        Vec::new()
+      },
+      strip:           name == "release",
+    })
+  }
+
+  /// Resolve the profile `name`, options set in `profiles` take precedence
+  /// over the built-in defaults
+  ///
+  /// Returns `None` if `name` is neither built-in nor defined in `profiles`.
+  pub fn resolve(
+    name: &str,
+    compiler: &str,
+    profiles: Option<&BTreeMap<String, divina_config::Profile>>,
+  ) -> Option<Self> {
+    let configured = profiles.and_then(|profiles| profiles.get(name));
+    let mut profile = match (Self::builtin(name, compiler), configured) {
+      (Some(profile), _) => profile,
+      (None, Some(_)) =>
+        Self {
+          name:            name.to_string(),
+          compile_options: Vec::new(),
+          link_options:    Vec::new(),
+          strip:           false,
+        },
+      (None, None) => return None,
+    };
+
+    if let Some(configured) = configured {
+      if let Some(compile_options) = &configured.compile_options {
+        profile.compile_options.clone_from(compile_options);
+      }
+      if let Some(link_options) = &configured.link_options {
+        profile.link_options.clone_from(link_options);
+      }
+      if let Some(strip) = configured.strip {
+        profile.strip = strip;
+      }
+    }
+
+    Some(profile)
+  }
+
+  /// Macro which is defined for every source built with the profile, such as
+  /// `DIVINA_PROFILE_RELEASE`
+  ///
+  /// Characters which can't appear in a macro name, such as the `-` of
+  /// `my-profile`, are replaced with `_`.
+  pub fn macro_name(&self) -> String {
+    format!(
+      "DIVINA_PROFILE_{}",
+      self
+        .name
+        .chars()
+        .map(|c| {
+          if c.is_ascii_alphanumeric() {
+            c.to_ascii_uppercase()
+          } else {
+            '_'
+          }
+        })
+        .collect::<String>()
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Profile;
+
+  #[test]
+  fn macro_name_is_a_valid_identifier() {
+    let profile = |name: &str| {
+      Profile {
+        name:            name.to_string(),
+        compile_options: Vec::new(),
+        link_options:    Vec::new(),
+        strip:           false,
+      }
+    };
+
+    assert_eq!(profile("release").macro_name(), "DIVINA_PROFILE_RELEASE");
+    assert_eq!(profile("my-prof").macro_name(), "DIVINA_PROFILE_MY_PROF");
+    assert_eq!(profile("ci.fast").macro_name(), "DIVINA_PROFILE_CI_FAST");
+  }
+}
@@ -0,0 +1,364 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Expected-output tests of a package's binary, declared in `Package.tests`
+
+use std::{
+  fs,
+  io::{Read, Write},
+  process::{ExitStatus, Stdio},
+  sync::{Arc, Mutex},
+  thread,
+  time::{Duration, Instant},
+};
+
+use wait_timeout::ChildExt;
+
+use crate::{memcheck, memcheck::Memcheck, plan::Invocation};
+
+/// Signal which a binary receives once it exceeds its CPU time limit, its
+/// number differs between Unix-like systems
+#[cfg(unix)]
+const SIGXCPU: i32 = libc::SIGXCPU;
+
+/// A test, resolved against its package's binary and directory
+#[derive(Debug, Clone)]
+pub struct TestCase {
+  pub package:      String,
+  pub name:         String,
+  pub binary:       String,
+  pub args:         Vec<String>,
+  pub stdin:        Option<String>,
+  pub stdout:       Option<String>,
+  pub stderr:       Option<String>,
+  pub golden:       Option<String>,
+  pub exit_code:    i32,
+  pub timeout:      Option<Duration>,
+  /// Whether the binary explains an unexpected exit code on its standard
+  /// output, as the harnesses of `Package.unit_tests` do
+  pub explains:     bool,
+  /// Seconds of CPU time after which the binary is killed
+  pub cpu_limit:    Option<u64>,
+  /// MiB of address space which the binary may use, not enforced under
+  /// memcheck as Valgrind itself reserves far more
+  pub memory_limit: Option<u64>,
+  /// Whether the binary is run under Valgrind's memcheck, failing the test on
+  /// any error which it reports
+  pub memcheck:     bool,
+  /// Why the test couldn't be prepared, such as a harness which didn't
+  /// compile, the test fails with it rather than being run
+  pub unprepared:   Option<String>,
+}
+
+/// The outcome of a single test
+#[derive(Debug, Clone)]
+pub struct TestResult {
+  pub package:  String,
+  pub name:     String,
+  pub duration: f64,
+  pub stdout:   String,
+  pub stderr:   String,
+  /// Why the test failed, a test without failures passed
+  pub failures: Vec<String>,
+  /// Whether the golden file was updated with the binary's output
+  pub blessed:  bool,
+}
+impl TestResult {
+  #[must_use]
+  pub fn passed(&self) -> bool { self.failures.is_empty() }
+}
+
+/// Line-wise diff of `expected` and `actual`, prefixed like a unified diff
+fn diff(expected: &str, actual: &str) -> String {
+  similar::TextDiff::from_lines(expected, actual)
+    .iter_all_changes()
+    .map(|change| {
+      format!(
+        "{}{}",
+        match change.tag() {
+          similar::ChangeTag::Delete => "-",
+          similar::ChangeTag::Insert => "+",
+          similar::ChangeTag::Equal => " ",
+        },
+        change.value().trim_end_matches('\n')
+      )
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// Read the whole of `stream` on another thread, so that a binary which fills
+/// one pipe does not block while Divina waits on another
+///
+/// The output is shared rather than returned, as processes which the binary
+/// spawned may hold the pipe open after it has been killed.
+fn drain<R: Read + Send + 'static>(
+  stream: Option<R>,
+) -> (thread::JoinHandle<()>, Arc<Mutex<Vec<u8>>>) {
+  let output = Arc::new(Mutex::new(Vec::new()));
+  let shared = Arc::clone(&output);
+  let reader = thread::spawn(move || {
+    if let Some(mut stream) = stream {
+      let mut buffer = [0; 4096];
+
+      while let Ok(read) = stream.read(&mut buffer) {
+        if read == 0 {
+          break;
+        }
+
+        shared
+          .lock()
+          .expect("!! could not lock test output, this *shouldn't* be possible")
+          .extend_from_slice(&buffer[..read]);
+      }
+    }
+  });
+
+  (reader, output)
+}
+
+/// Everything which `drain` has read so far
+fn drained(output: &Arc<Mutex<Vec<u8>>>) -> String {
+  String::from_utf8_lossy(
+    &output
+      .lock()
+      .expect("!! could not lock test output, this *shouldn't* be possible"),
+  )
+  .to_string()
+}
+
+impl TestCase {
+  /// Run the binary, killing it if it outlives the test's timeout
+  ///
+  /// Returns the binary's exit status, or `None` if it timed out, along with
+  /// its standard output and standard error.
+  fn execute(
+    &self,
+    memcheck: Option<&Memcheck>,
+  ) -> std::io::Result<(Option<ExitStatus>, String, String)> {
+    let mut invocation = Invocation {
+      program:   self.binary.clone(),
+      arguments: self.args.clone(),
+    };
+
+    if let Some(memcheck) = memcheck {
+      // The shell which applies limits would only exit with `127`
+      if !Memcheck::installed() {
+        return Err(std::io::Error::new(
+          std::io::ErrorKind::NotFound,
+          "could not run 'valgrind', perhaps it isn't installed ?",
+        ));
+      }
+
+      invocation = memcheck.wrap(invocation);
+    }
+
+    invocation = memcheck::limit(
+      invocation,
+      self.cpu_limit,
+      self.memory_limit.filter(|_| memcheck.is_none()),
+    );
+
+    let mut child = invocation
+      .command()
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()
+      .map_err(|error| {
+        // Name what couldn't be run, which isn't the binary under memcheck
+        std::io::Error::new(
+          error.kind(),
+          format!("could not run '{}': {}", invocation.program, error),
+        )
+      })?;
+    let stdin = self.stdin.clone().unwrap_or_default();
+    let mut child_stdin = child.stdin.take();
+    let writer = thread::spawn(move || {
+      if let Some(child_stdin) = &mut child_stdin {
+        // A binary which exits without reading its input closes the pipe
+        let _ = child_stdin.write_all(stdin.as_bytes());
+      }
+    });
+    let (stdout_reader, stdout) = drain(child.stdout.take());
+    let (stderr_reader, stderr) = drain(child.stderr.take());
+    let status = match self.timeout {
+      Some(timeout) => child.wait_timeout(timeout)?,
+      None => Some(child.wait()?),
+    };
+
+    if status.is_none() {
+      let _ = child.kill();
+      let _ = child.wait();
+    } else {
+      let _ = writer.join();
+      let _ = stdout_reader.join();
+      let _ = stderr_reader.join();
+    }
+
+    Ok((status, drained(&stdout), drained(&stderr)))
+  }
+
+  /// Why a binary which was terminated by a signal may have been
+  fn explain_signal(&self, status: ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+      use std::os::unix::process::ExitStatusExt;
+
+      match (status.signal(), self.cpu_limit) {
+        (Some(SIGXCPU), Some(cpu_limit)) =>
+          return format!("exceeded its CPU time limit of {}s", cpu_limit),
+        (Some(signal), _) => {
+          // Some kernels kill a binary outright once it reaches a limit
+          let limits = self
+            .cpu_limit
+            .map(|cpu_limit| format!("CPU time limit of {}s", cpu_limit))
+            .into_iter()
+            .chain(
+              self
+                .memory_limit
+                .map(|memory_limit| format!("memory limit of {} MiB", memory_limit)),
+            )
+            .collect::<Vec<_>>();
+
+          return format!(
+            "terminated by signal {}, expected exit code {}{}",
+            signal,
+            self.exit_code,
+            if limits.is_empty() {
+              String::new()
+            } else {
+              format!(", perhaps it exceeded its {}", limits.join(" or "))
+            }
+          );
+        }
+        _ => {}
+      }
+    }
+
+    format!(
+      "terminated by {:?}, expected exit code {}",
+      status, self.exit_code
+    )
+  }
+
+  /// Run the test, writing the binary's output to the golden file instead of
+  /// comparing against it if `bless` is set
+  ///
+  /// # Panics
+  /// if caller has insufficient permissions to write the golden file
+  #[must_use]
+  pub fn run(&self, bless: bool) -> TestResult {
+    let started = Instant::now();
+
+    if let Some(reason) = &self.unprepared {
+      return TestResult {
+        package:  self.package.clone(),
+        name:     self.name.clone(),
+        duration: 0.0,
+        stdout:   String::new(),
+        stderr:   String::new(),
+        failures: vec![reason.clone()],
+        blessed:  false,
+      };
+    }
+
+    let mut failures = Vec::new();
+    let mut blessed = false;
+    let memcheck = if self.memcheck {
+      let memcheck = Memcheck::new(&self.binary);
+
+      // A report of a previous run would be mistaken for this one's
+      let _ = fs::remove_file(&memcheck.log);
+
+      Some(memcheck)
+    } else {
+      None
+    };
+    log::debug!("{} @@ running test '{}'", self.package, self.name);
+
+    let (status, stdout, stderr) = self.execute(memcheck.as_ref()).unwrap_or_else(|error| {
+      failures.push(error.to_string());
+
+      (None, String::new(), String::new())
+    });
+
+    match status {
+      None if failures.is_empty() =>
+        failures.push(format!(
+          "timed out after {}s",
+          self.timeout.map_or(0.0, |timeout| timeout.as_secs_f64())
+        )),
+      Some(status) if status.code() != Some(self.exit_code) =>
+        failures.push(
+          if self.explains && status.code().is_some() && !stdout.is_empty() {
+            stdout.trim_end().to_string()
+          } else {
+            status.code().map_or_else(
+              || self.explain_signal(status),
+              |code| format!("exit code {}, expected {}", code, self.exit_code),
+            )
+          },
+        ),
+      _ => {}
+    }
+
+    if let Some(report) = memcheck.as_ref().and_then(Memcheck::errors) {
+      failures.push(format!("memcheck reported errors:\n{}", report.trim_end()));
+    }
+
+    if let Some(golden) = &self.golden {
+      if bless {
+        fs::write(golden, &stdout)
+          .unwrap_or_else(|_| panic!("!! could not write '{}', check permissions", golden));
+        blessed = true;
+      } else {
+        match fs::read_to_string(golden) {
+          Ok(expected) =>
+            if expected != stdout {
+              failures.push(format!(
+                "stdout differs from '{}':\n{}",
+                golden,
+                diff(&expected, &stdout)
+              ));
+            },
+          Err(_) =>
+            failures.push(format!(
+              "could not read golden file '{}', create it with `--bless`",
+              golden
+            )),
+        }
+      }
+    }
+
+    if let Some(expected) = &self.stdout {
+      if expected != &stdout {
+        failures.push(format!("stdout differs:\n{}", diff(expected, &stdout)));
+      }
+    }
+
+    if let Some(expected) = &self.stderr {
+      if expected != &stderr {
+        failures.push(format!("stderr differs:\n{}", diff(expected, &stderr)));
+      }
+    }
+
+    log::debug!(
+      "{} @@ test '{}' finished with {:?} after {:.3}s",
+      self.package,
+      self.name,
+      status,
+      started.elapsed().as_secs_f64()
+    );
+
+    TestResult {
+      package: self.package.clone(),
+      name: self.name.clone(),
+      duration: started.elapsed().as_secs_f64(),
+      stdout,
+      stderr,
+      failures,
+      blessed,
+    }
+  }
+}
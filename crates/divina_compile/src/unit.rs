@@ -0,0 +1,225 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! C harnesses which call the functions declared in `Package.unit_tests`
+
+use std::collections::BTreeMap;
+
+use divina_config::UnitTest;
+
+/// How a return value is widened and printed, chosen from the return type of
+/// a function's prototype
+fn conversion(return_type: &str) -> Option<(&'static str, &'static str)> {
+  let words = return_type
+    .split(|c: char| !is_identifier(c))
+    .filter(|word| !word.is_empty())
+    .collect::<Vec<_>>();
+
+  if return_type == "void" {
+    None
+  } else if words.iter().any(|word| matches!(*word, "float" | "double")) {
+    Some(("double", "%g"))
+  } else if words
+    .iter()
+    .any(|word| *word == "unsigned" || *word == "size_t" || word.starts_with("uint"))
+  {
+    Some(("unsigned long long", "%llu"))
+  } else {
+    Some(("long long", "%lld"))
+  }
+}
+
+/// Whether `c` may appear in a C identifier
+const fn is_identifier(c: char) -> bool { c.is_ascii_alphanumeric() || c == '_' }
+
+/// Return type of `signature`, everything before the `symbol` which is called
+///
+/// `symbol` is only matched as a whole identifier followed by its parameters,
+/// so that `t` isn't found within the `int` of `int t(int)`.
+fn return_type<'a>(signature: &'a str, symbol: &str) -> Option<&'a str> {
+  signature
+    .match_indices(symbol)
+    .map(|(position, _)| position)
+    .find(|&position| {
+      !signature[..position].ends_with(is_identifier)
+        && signature[position + symbol.len()..]
+          .trim_start()
+          .starts_with('(')
+    })
+    .map(|position| signature[..position].trim())
+}
+
+/// Render a harness which runs the case whose index it is passed as its
+/// first argument, exiting unsuccessfully and explaining why on its standard
+/// output if the function returned something unexpected
+///
+/// Returns the harness along with the name of each case, in index order.
+pub fn harness(package: &str, unit_tests: &BTreeMap<String, UnitTest>) -> (String, Vec<String>) {
+  let mut prototypes = Vec::new();
+  let mut cases = Vec::new();
+  let mut names = Vec::new();
+
+  for (symbol, unit_test) in unit_tests {
+    let signature = unit_test.signature.as_ref().unwrap_or_else(|| {
+      divina_util::exit_with!(
+        1,
+        "!! {} @@ could not access `Package.unit_tests.{}.signature`, perhaps you've forgotten to \
+         assign it ?",
+        package,
+        symbol
+      );
+    });
+    let return_type = return_type(signature, symbol).unwrap_or_else(|| {
+      divina_util::exit_with!(
+        1,
+        "!! {} @@ `Package.unit_tests.{}.signature` does not declare a function named '{}'",
+        package,
+        symbol,
+        symbol
+      );
+    });
+
+    prototypes.push(format!("{};", signature.trim().trim_end_matches(';')));
+
+    for case in unit_test.cases.as_deref().unwrap_or_default() {
+      let call = format!(
+        "{}({})",
+        symbol,
+        case.args.as_deref().unwrap_or_default().join(", ")
+      );
+
+      cases.push(format!("  case {}: {{", names.len()));
+
+      if let (Some((widened, format)), Some(expected)) = (conversion(return_type), &case.expected) {
+        cases.push(format!("    {} actual = ({})({});", widened, widened, call));
+        cases.push(format!(
+          "    {} expected = ({})({});",
+          widened, widened, expected
+        ));
+        cases.push(String::new());
+        cases.push("    if (actual != expected) {".to_string());
+        cases.push(format!(
+          "      printf(\"returned {}, expected {}\\n\", actual, expected);",
+          format, format
+        ));
+        cases.push("      return 1;".to_string());
+        cases.push("    }".to_string());
+      } else {
+        cases.push(format!("    {};", call));
+      }
+
+      cases.push(String::new());
+      cases.push("    return 0;".to_string());
+      cases.push("  }".to_string());
+      names.push(call);
+    }
+  }
+
+  let mut lines = vec![
+    "/* Generated by Divina, changes will be overwritten */".to_string(),
+    "#include <stdio.h>".to_string(),
+    "#include <stdlib.h>".to_string(),
+    String::new(),
+  ];
+
+  lines.extend(prototypes);
+  lines.push(String::new());
+  lines.push("int main(int argc, char **argv) {".to_string());
+  lines.push("  switch (argc > 1 ? atoi(argv[1]) : -1) {".to_string());
+  lines.extend(cases);
+  lines.push("  default:".to_string());
+  lines.push("    printf(\"unknown case\\n\");".to_string());
+  lines.push(String::new());
+  lines.push("    return 2;".to_string());
+  lines.push("  }".to_string());
+  lines.push("}".to_string());
+
+  (format!("{}\n", lines.join("\n")), names)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use divina_config::{UnitTest, UnitTestCase};
+
+  use super::{conversion, harness, return_type};
+
+  fn unit_test(signature: &str, args: &[&str], expected: Option<&str>) -> UnitTest {
+    UnitTest {
+      signature: Some(signature.to_string()),
+      cases:     Some(vec![UnitTestCase {
+        args:     Some(args.iter().map(ToString::to_string).collect()),
+        expected: expected.map(ToString::to_string),
+      }]),
+    }
+  }
+
+  #[test]
+  fn return_type_skips_the_symbol_within_other_identifiers() {
+    assert_eq!(return_type("size_t size(void)", "size"), Some("size_t"));
+    assert_eq!(return_type("int t(int)", "t"), Some("int"));
+    assert_eq!(return_type("long add (long, long)", "add"), Some("long"));
+    assert_eq!(
+      return_type("unsigned char *at(int)", "at"),
+      Some("unsigned char *")
+    );
+    assert_eq!(return_type("int tt(int)", "t"), None);
+    assert_eq!(return_type("int t", "t"), None);
+  }
+
+  #[test]
+  fn conversion_follows_the_return_type() {
+    assert_eq!(conversion("void"), None);
+    assert_eq!(conversion("double"), Some(("double", "%g")));
+    assert_eq!(conversion("const float"), Some(("double", "%g")));
+    assert_eq!(conversion("size_t"), Some(("unsigned long long", "%llu")));
+    assert_eq!(conversion("uint32_t"), Some(("unsigned long long", "%llu")));
+    assert_eq!(
+      conversion("unsigned int"),
+      Some(("unsigned long long", "%llu"))
+    );
+    assert_eq!(conversion("ssize_t"), Some(("long long", "%lld")));
+    assert_eq!(conversion("long"), Some(("long long", "%lld")));
+  }
+
+  #[test]
+  fn harness_compares_with_the_return_types_conversion() {
+    let mut unit_tests = BTreeMap::new();
+
+    unit_tests.insert(
+      "size".to_string(),
+      unit_test("size_t size(void)", &[], Some("4")),
+    );
+    unit_tests.insert(
+      "t".to_string(),
+      unit_test("int t(int);", &["2"], Some("-2")),
+    );
+
+    let (harness, names) = harness("package", &unit_tests);
+
+    assert_eq!(names, vec!["size()", "t(2)"]);
+    assert!(harness.contains("size_t size(void);\nint t(int);\n"));
+    assert!(harness
+      .contains("  case 0: {\n    unsigned long long actual = (unsigned long long)(size());"));
+    assert!(harness.contains("  case 1: {\n    long long actual = (long long)(t(2));"));
+    assert!(harness.contains("printf(\"returned %llu, expected %llu\\n\", actual, expected);"));
+    assert!(harness.contains("printf(\"returned %lld, expected %lld\\n\", actual, expected);"));
+  }
+
+  #[test]
+  fn harness_only_calls_void_functions() {
+    let mut unit_tests = BTreeMap::new();
+
+    unit_tests.insert(
+      "reset".to_string(),
+      unit_test("void reset(void)", &[], None),
+    );
+
+    let (harness, names) = harness("package", &unit_tests);
+
+    assert_eq!(names, vec!["reset()"]);
+    assert!(harness.contains("  case 0: {\n    reset();\n\n    return 0;\n  }"));
+    assert!(!harness.contains("actual"));
+  }
+}
@@ -0,0 +1,121 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Conversions of nested tables which say which field couldn't be converted,
+//! rather than ignoring the whole table like `get_or_none!` does
+
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+use rlua::{Context, FromLua, Table, Value};
+
+/// A field which couldn't be converted, `path` is relative to the table which
+/// was being converted, such as `.hello.exit_code`
+#[derive(Debug)]
+struct FieldError {
+  path:  String,
+  cause: String,
+}
+impl fmt::Display for FieldError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "`{}`, {}", self.path, self.cause)
+  }
+}
+impl std::error::Error for FieldError {}
+
+/// Prefix the path of `error` with `segment`, the field which it was raised
+/// within
+fn nest(segment: &str, error: &rlua::Error) -> rlua::Error {
+  let field = match error {
+    rlua::Error::ExternalError(inner) =>
+      inner.downcast_ref::<FieldError>().map(|field| {
+        FieldError {
+          path:  format!("{}{}", segment, field.path),
+          cause: field.cause.clone(),
+        }
+      }),
+    _ => None,
+  };
+
+  rlua::Error::ExternalError(Arc::new(field.unwrap_or_else(|| {
+    FieldError {
+      path:  segment.to_string(),
+      cause: crate::describe(error),
+    }
+  })))
+}
+
+/// Get `key` of `table`, naming it if it can't be converted
+pub fn field<'lua, T: FromLua<'lua>>(table: &Table<'lua>, key: &str) -> rlua::Result<T> {
+  table
+    .get(key)
+    .map_err(|error| nest(&format!(".{}", key), &error))
+}
+
+/// A table of values by name, naming the entry which can't be converted
+#[derive(Debug)]
+pub struct Map<T>(pub BTreeMap<String, T>);
+impl<'lua, T: FromLua<'lua>> FromLua<'lua> for Map<T> {
+  fn from_lua(value: Value<'lua>, ctx: Context<'lua>) -> rlua::Result<Self> {
+    let mut map = BTreeMap::new();
+
+    for pair in Table::from_lua(value, ctx)?.pairs::<String, Value<'lua>>() {
+      let (key, value) = pair?;
+      let value = T::from_lua(value, ctx).map_err(|error| nest(&format!(".{}", key), &error))?;
+
+      map.insert(key, value);
+    }
+
+    Ok(Self(map))
+  }
+}
+
+/// A sequence of values, naming the entry which can't be converted
+#[derive(Debug)]
+pub struct List<T>(pub Vec<T>);
+impl<'lua, T: FromLua<'lua>> FromLua<'lua> for List<T> {
+  fn from_lua(value: Value<'lua>, ctx: Context<'lua>) -> rlua::Result<Self> {
+    Table::from_lua(value, ctx)?
+      .sequence_values::<Value<'lua>>()
+      .enumerate()
+      .map(|(index, value)| {
+        // Lua counts from one
+        T::from_lua(value?, ctx).map_err(|error| nest(&format!("[{}]", index + 1), &error))
+      })
+      .collect::<rlua::Result<_>>()
+      .map(Self)
+  }
+}
+
+/// Get `key` of `table`, `None` if it isn't assigned
+///
+/// # Panics
+/// exits if it is assigned but can't be converted, naming the offending field
+/// of `from_table`
+pub fn get<'lua, T: FromLua<'lua> + fmt::Debug>(
+  table: &Table<'lua>,
+  from_table: &str,
+  key: &str,
+) -> Option<T> {
+  let value = field::<Option<T>>(table, key).unwrap_or_else(|error| {
+    let (path, cause) = match &error {
+      rlua::Error::ExternalError(inner) =>
+        inner.downcast_ref::<FieldError>().map_or_else(
+          || (format!(".{}", key), inner.to_string()),
+          |field| (field.path.clone(), field.cause.clone()),
+        ),
+      _ => (format!(".{}", key), crate::describe(&error)),
+    };
+
+    divina_util::exit_with!(
+      1,
+      "!! could not read `{}{}`, perhaps it has the wrong type ? ({})",
+      from_table,
+      path,
+      cause
+    );
+  });
+
+  log::trace!("read `{}.{}` as {:?}", from_table, key, value);
+
+  value
+}
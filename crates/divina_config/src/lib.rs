@@ -16,9 +16,12 @@
   html_favicon_url = "https://emojipedia-us.s3.dualstack.us-west-1.amazonaws.com/thumbs/160/twitter/282/ribbon_1f380.png"
 )]
 
-use std::{fmt, fmt::Formatter, io::Read};
+mod field;
+mod library;
 
-use rlua::{Lua, Table};
+use std::{collections::BTreeMap, fmt, fmt::Formatter, io::Read, path::Path, rc::Rc};
+
+use rlua::{Context, FromLua, Function, Lua, RegistryKey, Table, Value};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -63,7 +66,8 @@ macro_rules! get_or_none {
         "could not access required global `{}.{}`, perhaps you've forgotten to define it ?",
         $from_table, $key,
       )))
-    }
+    };
+    log::trace!("read `{}.{}` as {:?}", $from_table, $key, $assign_to);
   };
 }
 #[macro_export]
@@ -115,7 +119,8 @@ macro_rules! get_enum_or_none {
         )))
         .expect($error_message),
       )
-    }
+    };
+    log::trace!("read `{}.{}` as {:?}", $from_table, $key, $assign_to);
   };
 }
 
@@ -131,7 +136,7 @@ impl fmt::Display for ConfigType {
   }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PackageType {
   Bin = 1,
   Lib = 2,
@@ -171,6 +176,244 @@ impl Arch {
   }
 }
 
+/// Options of a build profile, such as `Package.profiles.release`
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+  pub compile_options: Option<Vec<String>>,
+  pub link_options:    Option<Vec<String>>,
+  pub strip:           Option<bool>,
+}
+impl<'lua> FromLua<'lua> for Profile {
+  fn from_lua(value: Value<'lua>, ctx: Context<'lua>) -> rlua::Result<Self> {
+    let table = Table::from_lua(value, ctx)?;
+
+    Ok(Self {
+      compile_options: field::field(&table, "compile_options")?,
+      link_options:    field::field(&table, "link_options")?,
+      strip:           field::field(&table, "strip")?,
+    })
+  }
+}
+
+/// An expected-output test of a package's binary, such as
+/// `Package.tests.hello`
+#[derive(Debug, Clone, Default)]
+pub struct Test {
+  pub args:         Option<Vec<String>>,
+  pub stdin:        Option<String>,
+  pub stdout:       Option<String>,
+  pub stderr:       Option<String>,
+  /// File which holds the expected standard output, relative to the package
+  pub golden:       Option<String>,
+  pub exit_code:    Option<i32>,
+  /// Seconds after which the binary is killed
+  pub timeout:      Option<f64>,
+  /// Seconds of CPU time after which the binary is killed
+  pub cpu_limit:    Option<u64>,
+  /// MiB of address space which the binary may use
+  pub memory_limit: Option<u64>,
+}
+impl<'lua> FromLua<'lua> for Test {
+  fn from_lua(value: Value<'lua>, ctx: Context<'lua>) -> rlua::Result<Self> {
+    let table = Table::from_lua(value, ctx)?;
+
+    Ok(Self {
+      args:         field::field(&table, "args")?,
+      stdin:        field::field(&table, "stdin")?,
+      stdout:       field::field(&table, "stdout")?,
+      stderr:       field::field(&table, "stderr")?,
+      golden:       field::field(&table, "golden")?,
+      exit_code:    field::field(&table, "exit_code")?,
+      timeout:      field::field(&table, "timeout")?,
+      cpu_limit:    field::field(&table, "cpu_limit")?,
+      memory_limit: field::field(&table, "memory_limit")?,
+    })
+  }
+}
+
+/// A benchmark of a package's binary, such as `Package.benches.sort`
+#[derive(Debug, Clone, Default)]
+pub struct Bench {
+  pub args:       Option<Vec<String>>,
+  pub stdin:      Option<String>,
+  /// Number of timed runs of the binary
+  pub iterations: Option<usize>,
+  /// Number of untimed runs of the binary, before the timed runs
+  pub warmup:     Option<usize>,
+}
+impl<'lua> FromLua<'lua> for Bench {
+  fn from_lua(value: Value<'lua>, ctx: Context<'lua>) -> rlua::Result<Self> {
+    let table = Table::from_lua(value, ctx)?;
+
+    Ok(Self {
+      args:       field::field(&table, "args")?,
+      stdin:      field::field(&table, "stdin")?,
+      iterations: field::field(&table, "iterations")?,
+      warmup:     field::field(&table, "warmup")?,
+    })
+  }
+}
+
+/// A function of a package which is tested through a generated C harness,
+/// such as `Package.unit_tests.add_numbers`
+#[derive(Debug, Clone, Default)]
+pub struct UnitTest {
+  /// C prototype of the function, such as `long add_numbers(long, long)`
+  pub signature: Option<String>,
+  pub cases:     Option<Vec<UnitTestCase>>,
+}
+impl<'lua> FromLua<'lua> for UnitTest {
+  fn from_lua(value: Value<'lua>, ctx: Context<'lua>) -> rlua::Result<Self> {
+    let table = Table::from_lua(value, ctx)?;
+
+    Ok(Self {
+      signature: field::field(&table, "signature")?,
+      cases:     field::field::<Option<field::List<_>>>(&table, "cases")?.map(|cases| cases.0),
+    })
+  }
+}
+
+/// Arguments which a function is called with and the value which it is
+/// expected to return, both as C expressions
+#[derive(Debug, Clone, Default)]
+pub struct UnitTestCase {
+  pub args:     Option<Vec<String>>,
+  pub expected: Option<String>,
+}
+impl<'lua> FromLua<'lua> for UnitTestCase {
+  fn from_lua(value: Value<'lua>, ctx: Context<'lua>) -> rlua::Result<Self> {
+    let table = Table::from_lua(value, ctx)?;
+
+    Ok(Self {
+      args:     field::field(&table, "args")?,
+      expected: field::field(&table, "expected")?,
+    })
+  }
+}
+
+/// A field of a struct in `Package.structs`, laid out after the field before
+/// it without padding
+#[derive(Debug, Clone, Default)]
+pub struct StructField {
+  pub name: Option<String>,
+  /// Size of the field in bytes
+  pub size: Option<usize>,
+}
+impl<'lua> FromLua<'lua> for StructField {
+  fn from_lua(value: Value<'lua>, ctx: Context<'lua>) -> rlua::Result<Self> {
+    let table = Table::from_lua(value, ctx)?;
+
+    Ok(Self {
+      name: field::field(&table, "name")?,
+      size: field::field(&table, "size")?,
+    })
+  }
+}
+
+/// Functions of `Package.hooks`, which Divina calls at points of the build,
+/// along with the `build` function which generates sources
+///
+/// The interpreter which evaluated `Divina.lua` is kept alive alongside them,
+/// as Lua functions can't outlive it.
+#[derive(Clone, Default)]
+pub struct Hooks {
+  lua:       Option<Rc<Lua>>,
+  functions: BTreeMap<String, Rc<RegistryKey>>,
+}
+impl Hooks {
+  /// Points of the build at which a hook may be called
+  pub const NAMES: [&'static str; 3] = ["pre_build", "post_compile", "post_link"];
+
+  /// Whether the hook `name` is defined
+  #[must_use]
+  pub fn contains(&self, name: &str) -> bool { self.functions.contains_key(name) }
+
+  /// Call the hook `name` with a table of `context`, if it is defined
+  ///
+  /// # Errors
+  /// if the hook raised an error
+  pub fn call(&self, name: &str, context: &BTreeMap<&str, String>) -> Result<(), String> {
+    let (lua, key) = match (&self.lua, self.functions.get(name)) {
+      (Some(lua), Some(key)) => (lua, key),
+      _ => return Ok(()),
+    };
+
+    log::debug!("calling hook `{}` with {:?}", name, context);
+
+    lua
+      .context(|ctx| {
+        let function = ctx.registry_value::<Function<'_>>(key)?;
+        let table = ctx.create_table()?;
+
+        for (key, value) in context {
+          table.set(*key, value.as_str())?;
+        }
+
+        function.call::<_, ()>(table)
+      })
+      .map_err(|error| describe(&error))
+  }
+}
+impl fmt::Debug for Hooks {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    f.debug_set().entries(self.functions.keys()).finish()
+  }
+}
+
+/// Describe `error`, an error raised by a Rust function such as
+/// `Divina.fs.read` is described by its cause rather than by the traceback
+/// which Lua wraps it in
+fn describe(error: &rlua::Error) -> String {
+  match error {
+    rlua::Error::CallbackError {
+      cause, ..
+    } => describe(cause),
+    rlua::Error::RuntimeError(message) => message.clone(),
+    _ => error.to_string(),
+  }
+}
+
+/// A read-only view of the environment, including the variables which
+/// `dotenv` loaded from `.env`
+///
+/// Variables are looked up as they are accessed, so the table itself is
+/// always empty and can't be iterated.
+fn env_table(ctx: Context<'_>) -> Table<'_> {
+  let table = ctx
+    .create_table()
+    .expect("!! could not create table `Divina.env`, this *shouldn't* be possible");
+  let metatable = ctx
+    .create_table()
+    .expect("!! could not create metatable of `Divina.env`, this *shouldn't* be possible");
+
+  metatable
+    .set(
+      "__index",
+      ctx
+        .create_function(|_, (_, name): (Table<'_>, String)| Ok(std::env::var(name).ok()))
+        .expect("!! could not create function `Divina.env.__index`, this *shouldn't* be possible"),
+    )
+    .expect("!! could not set field `Divina.env.__index`, this *shouldn't* be possible");
+  metatable
+    .set(
+      "__newindex",
+      ctx
+        .create_function(|_, (_, name, _): (Table<'_>, String, Value<'_>)| {
+          Err::<(), _>(rlua::Error::RuntimeError(format!(
+            "could not assign `Divina.env.{}`, `Divina.env` is read-only",
+            name
+          )))
+        })
+        .expect(
+          "!! could not create function `Divina.env.__newindex`, this *shouldn't* be possible",
+        ),
+    )
+    .expect("!! could not set field `Divina.env.__newindex`, this *shouldn't* be possible");
+  table.set_metatable(Some(metatable));
+
+  table
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
   pub name:                   Option<String>,
@@ -187,6 +430,22 @@ pub struct Config {
   pub arch:                   Option<Arch>,
   pub compiler:               Option<String>,
   pub visual_studio:          Option<String>,
+  pub include_dirs:           Option<Vec<String>>,
+  pub defines:                Option<BTreeMap<String, String>>,
+  pub profiles:               Option<BTreeMap<String, Profile>>,
+  pub listing:                Option<bool>,
+  pub map:                    Option<bool>,
+  pub tests:                  Option<BTreeMap<String, Test>>,
+  pub unit_tests:             Option<BTreeMap<String, UnitTest>>,
+  pub benches:                Option<BTreeMap<String, Bench>>,
+  pub hooks:                  Hooks,
+  /// Files which `Package.build` reads, relative to the package
+  pub build_inputs:           Option<Vec<String>>,
+  pub constants:              Option<BTreeMap<String, String>>,
+  pub structs:                Option<BTreeMap<String, Vec<StructField>>>,
+  /// Build profile selected on the command line, exposed to `Divina.lua` as
+  /// `Divina.profile`
+  pub profile:                Option<String>,
 }
 impl Config {
   /// Create a new `Config`
@@ -209,19 +468,24 @@ impl Config {
       )
     });
 
+    log::debug!("evaluating '{}'", file);
+
+    // Divina is always run from the workspace root, members are configured
+    // relative to it
+    let workspace_root = std::env::current_dir()
+      .expect("!! could not access the current directory, check permissions");
+    let package_dir = Path::new(file)
+      .parent()
+      .filter(|parent| !parent.as_os_str().is_empty())
+      .map_or_else(
+        || workspace_root.clone(),
+        |parent| workspace_root.join(parent),
+      );
     let lua = Lua::new();
 
     #[allow(clippy::cognitive_complexity)]
     lua.context(|ctx| {
       let globals = ctx.globals();
-      let test_function = ctx
-        .create_function(|_, ()| {
-          println!("test");
-
-          Ok(())
-        })
-        .expect("!! could not create function `test`, this *shouldn't* be possible");
-
       let divina_table = ctx
         .create_table()
         .expect("!! could not create table `Divina`, this *shouldn't* be possible");
@@ -249,6 +513,31 @@ impl Config {
       divina_table
         .set("version", VERSION)
         .expect("!! could not set field `Divina.version`, this *shouldn't* be possible");
+      divina_table
+        .set("os", std::env::consts::OS)
+        .expect("!! could not set field `Divina.os`, this *shouldn't* be possible");
+      divina_table
+        .set("host_arch", std::env::consts::ARCH)
+        .expect("!! could not set field `Divina.host_arch`, this *shouldn't* be possible");
+      divina_table
+        .set(
+          "profile",
+          // The compiler's default profile
+          self.profile.as_deref().unwrap_or("debug"),
+        )
+        .expect("!! could not set field `Divina.profile`, this *shouldn't* be possible");
+      divina_table
+        .set("env", env_table(ctx))
+        .expect("!! could not set field `Divina.env`, this *shouldn't* be possible");
+      divina_table
+        .set(
+          "workspace_root",
+          workspace_root.to_string_lossy().to_string(),
+        )
+        .expect("!! could not set field `Divina.workspace_root`, this *shouldn't* be possible");
+      divina_table
+        .set("package_dir", package_dir.to_string_lossy().to_string())
+        .expect("!! could not set field `Divina.package_dir`, this *shouldn't* be possible");
       divina_table
         .set("Type", type_table)
         .expect("!! could not set field `Divina.Type`, this *shouldn't* be possible");
@@ -256,19 +545,23 @@ impl Config {
         .set("Arch", arch_table)
         .expect("!! could not set field `Divina.Arch`, this *shouldn't* be possible");
 
+      library::register(ctx, &divina_table, &package_dir);
+
       globals
         .set("Divina", divina_table)
         .expect("!! could not set table `Divina`, this *shouldn't* be possible");
-      globals
-        .set("test", test_function)
-        .expect("!! could not set function `test`, this *shouldn't* be possible");
 
-      ctx.load(contents.as_bytes()).exec().unwrap_or_else(|_| {
-        panic!(
-          "!! could not execute `{}`, perhaps you've made a syntax error ?",
-          file
-        )
-      });
+      // Name the chunk after its file, so that errors raised by hooks say
+      // where they were raised
+      ctx
+        .load(contents.as_bytes())
+        .set_name(&format!("@{}", file))
+        .and_then(rlua::Chunk::exec)
+        // Errors raised by `Divina.fs` and friends are as likely as syntax
+        // errors, so say which it was
+        .unwrap_or_else(|error| panic!("!! could not execute `{}`: {}", file, describe(&error)));
+
+      log::trace!("executed '{}'", file);
 
       self.config_type = if globals.get::<_, Table<'_>>("Workspace").is_ok() {
         ConfigType::Workspace
@@ -283,6 +576,8 @@ impl Config {
         );
       };
 
+      log::debug!("'{}' describes a `{}`", file, self.config_type);
+
       if self.config_type == ConfigType::Package {
         get_table!(config_table, "Package", globals);
 
@@ -376,6 +671,128 @@ impl Config {
           (self.visual_studio),
           GetRequired::No
         );
+        get_or_none!(
+          config_table,
+          "Package",
+          "include_dirs",
+          Vec<String>,
+          (self.include_dirs),
+          GetRequired::No
+        );
+        self.defines =
+          field::get::<field::Map<String>>(&config_table, "Package", "defines").map(|map| map.0);
+        self.profiles =
+          field::get::<field::Map<Profile>>(&config_table, "Package", "profiles").map(|map| map.0);
+        get_or_none!(
+          config_table,
+          "Package",
+          "listing",
+          bool,
+          (self.listing),
+          GetRequired::No
+        );
+        get_or_none!(
+          config_table,
+          "Package",
+          "map",
+          bool,
+          (self.map),
+          GetRequired::No
+        );
+        self.tests =
+          field::get::<field::Map<Test>>(&config_table, "Package", "tests").map(|map| map.0);
+        self.unit_tests =
+          field::get::<field::Map<UnitTest>>(&config_table, "Package", "unit_tests")
+            .map(|map| map.0);
+        self.benches =
+          field::get::<field::Map<Bench>>(&config_table, "Package", "benches").map(|map| map.0);
+
+        if let Ok(hooks) = config_table.get::<_, Table<'_>>("hooks") {
+          for pair in hooks.pairs::<String, Function<'_>>() {
+            let (name, function) = pair.unwrap_or_else(|_| {
+              divina_util::exit_with!(
+                1,
+                "!! could not access `Package.hooks`, perhaps one of them isn't a function ?"
+              );
+            });
+
+            if !Hooks::NAMES.contains(&name.as_str()) {
+              divina_util::exit_with!(
+                1,
+                "!! `Package.hooks.{}` is not a hook, perhaps you meant one of: {} ?",
+                name,
+                Hooks::NAMES.join(", ")
+              );
+            }
+
+            log::trace!("read hook `Package.hooks.{}`", name);
+            self.hooks.functions.insert(
+              name,
+              Rc::new(
+                ctx
+                  .create_registry_value(function)
+                  .expect("!! could not store `Package.hooks`, this *shouldn't* be possible"),
+              ),
+            );
+          }
+        }
+
+        get_or_none!(
+          config_table,
+          "Package",
+          "build_inputs",
+          Vec<String>,
+          (self.build_inputs),
+          GetRequired::No
+        );
+        // Numbers are coerced into strings, strings are kept as expressions
+        self.constants =
+          field::get::<field::Map<String>>(&config_table, "Package", "constants").map(|map| map.0);
+        self.structs =
+          field::get::<field::Map<field::List<StructField>>>(&config_table, "Package", "structs")
+            .map(|structs| {
+              structs
+                .0
+                .into_iter()
+                .map(|(name, fields)| (name, fields.0))
+                .collect()
+            });
+
+        // `Package.build` takes precedence over a `build.lua` beside `Divina.lua`
+        let build_script = std::path::Path::new(file).with_file_name("build.lua");
+        let build = config_table
+          .get::<_, Function<'_>>("build")
+          .ok()
+          .or_else(|| {
+            let contents = std::fs::read_to_string(&build_script).ok()?;
+
+            log::debug!("loading '{}'", build_script.display());
+
+            Some(
+              ctx
+                .load(contents.as_bytes())
+                .set_name(&format!("@{}", build_script.display()))
+                .and_then(rlua::Chunk::into_function)
+                .unwrap_or_else(|_| {
+                  divina_util::exit_with!(
+                    1,
+                    "!! could not load `{}`, perhaps you've made a syntax error ?",
+                    build_script.display()
+                  );
+                }),
+            )
+          });
+
+        if let Some(build) = build {
+          self.hooks.functions.insert(
+            "build".to_string(),
+            Rc::new(
+              ctx
+                .create_registry_value(build)
+                .expect("!! could not store `Package.build`, this *shouldn't* be possible"),
+            ),
+          );
+        }
       } else {
         get_table!(workspace_table, "Workspace", globals);
 
@@ -397,7 +814,12 @@ impl Config {
           self.members = Some(Vec::new());
 
           for path in tables {
-            let mut config = Self::new();
+            log::debug!("configuring workspace member '{}'", path);
+
+            let mut config = Self {
+              profile: self.profile.clone(),
+              ..Self::new()
+            };
             config.configure(&format!("{}/Divina.lua", path));
             config.path = Some(path);
 
@@ -410,6 +832,10 @@ impl Config {
         }
       }
     });
+
+    if !self.hooks.functions.is_empty() {
+      self.hooks.lua = Some(Rc::new(lua));
+    }
   }
 }
 impl Default for Config {
@@ -429,6 +855,19 @@ impl Default for Config {
       arch:                   None,
       compiler:               None,
       visual_studio:          None,
+      include_dirs:           None,
+      defines:                None,
+      profiles:               None,
+      listing:                None,
+      map:                    None,
+      tests:                  None,
+      unit_tests:             None,
+      benches:                None,
+      hooks:                  Hooks::default(),
+      build_inputs:           None,
+      constants:              None,
+      structs:                None,
+      profile:                None,
     }
   }
 }
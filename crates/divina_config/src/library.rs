@@ -0,0 +1,347 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Utilities which Lua lacks, registered on the `Divina` table
+//!
+//! Relative paths are resolved against the directory of the `Divina.lua`
+//! being evaluated, like `Package.sources` are.
+
+use std::{
+  cmp::Ordering,
+  path::{Path, PathBuf},
+  process::Command,
+};
+
+use rlua::{Context, Table, Variadic};
+
+/// Raise `message` as a Lua error
+const fn raise<T>(message: String) -> rlua::Result<T> { Err(rlua::Error::RuntimeError(message)) }
+
+fn parse_version(function: &str, version: &str) -> rlua::Result<semver::Version> {
+  semver::Version::parse(version).or_else(|error| {
+    raise(format!(
+      "`Divina.semver.{}` could not parse version '{}': {}",
+      function, version, error
+    ))
+  })
+}
+
+/// `-1`, `0` or `1` if `left` is older than, the same as or newer than
+/// `right`
+fn compare(left: &str, right: &str) -> rlua::Result<i8> {
+  Ok(
+    match parse_version("compare", left)?.cmp(&parse_version("compare", right)?) {
+      Ordering::Less => -1,
+      Ordering::Equal => 0,
+      Ordering::Greater => 1,
+    },
+  )
+}
+
+/// Whether `version` matches `requirement`, such as `>=0.1.0, <0.2.0`
+fn satisfies(version: &str, requirement: &str) -> rlua::Result<bool> {
+  let parsed = semver::VersionReq::parse(requirement).or_else(|error| {
+    raise(format!(
+      "`Divina.semver.satisfies` could not parse requirement '{}': {}",
+      requirement, error
+    ))
+  })?;
+
+  Ok(parsed.matches(&parse_version("satisfies", version)?))
+}
+
+/// `path` relative to `base`, both relative to `directory` unless absolute
+fn relative(directory: &Path, path: &str, base: &str) -> rlua::Result<String> {
+  pathdiff::diff_paths(directory.join(path), directory.join(base)).map_or_else(
+    || {
+      raise(format!(
+        "`Divina.path.relative` could not make '{}' relative to '{}'",
+        path, base
+      ))
+    },
+    |relative| Ok(relative.to_string_lossy().to_string()),
+  )
+}
+
+/// Create a table named `name` on `table`, filled in by `fill`
+fn set_table<'lua>(
+  ctx: Context<'lua>,
+  table: &Table<'lua>,
+  name: &str,
+  fill: impl FnOnce(&Table<'lua>),
+) {
+  let nested = ctx.create_table().unwrap_or_else(|_| {
+    panic!(
+      "!! could not create table `Divina.{}`, this *shouldn't* be possible",
+      name
+    )
+  });
+
+  fill(&nested);
+  table.set(name, nested).unwrap_or_else(|_| {
+    panic!(
+      "!! could not set table `Divina.{}`, this *shouldn't* be possible",
+      name
+    )
+  });
+}
+
+/// Register `function` as `name` on `table`, `path` is only used to describe
+/// it if that fails
+fn set_function<'lua>(
+  table: &Table<'lua>,
+  path: &str,
+  name: &str,
+  function: rlua::Result<rlua::Function<'lua>>,
+) {
+  function
+    .and_then(|function| table.set(name, function))
+    .unwrap_or_else(|_| {
+      panic!(
+        "!! could not set function `{}.{}`, this *shouldn't* be possible",
+        path, name
+      )
+    });
+}
+
+/// `Divina.fs.exists`, `Divina.fs.read` and `Divina.fs.list`
+fn register_fs<'lua>(ctx: Context<'lua>, divina_table: &Table<'lua>, package_dir: &Path) {
+  set_table(ctx, divina_table, "fs", |fs| {
+    let directory = package_dir.to_path_buf();
+    set_function(
+      fs,
+      "Divina.fs",
+      "exists",
+      ctx.create_function(move |_, path: String| Ok(directory.join(path).exists())),
+    );
+
+    let directory = package_dir.to_path_buf();
+    set_function(
+      fs,
+      "Divina.fs",
+      "read",
+      ctx.create_function(move |_, path: String| {
+        std::fs::read_to_string(directory.join(&path)).or_else(|error| {
+          raise(format!(
+            "`Divina.fs.read` could not read '{}': {}",
+            path, error
+          ))
+        })
+      }),
+    );
+
+    let directory = package_dir.to_path_buf();
+    set_function(
+      fs,
+      "Divina.fs",
+      "list",
+      ctx.create_function(move |_, path: String| {
+        let entries = std::fs::read_dir(directory.join(&path)).or_else(|error| {
+          raise(format!(
+            "`Divina.fs.list` could not list '{}': {}",
+            path, error
+          ))
+        })?;
+        let mut names = entries
+          .flatten()
+          .map(|entry| entry.file_name().to_string_lossy().to_string())
+          .collect::<Vec<_>>();
+
+        // `read_dir`'s order is platform-dependent
+        names.sort();
+
+        Ok(names)
+      }),
+    );
+  });
+}
+
+/// `Divina.path.join` and `Divina.path.relative`
+fn register_path<'lua>(ctx: Context<'lua>, divina_table: &Table<'lua>, package_dir: &Path) {
+  set_table(ctx, divina_table, "path", |path| {
+    set_function(
+      path,
+      "Divina.path",
+      "join",
+      ctx.create_function(|_, parts: Variadic<String>| {
+        Ok(
+          parts
+            .iter()
+            .collect::<PathBuf>()
+            .to_string_lossy()
+            .to_string(),
+        )
+      }),
+    );
+
+    let directory = package_dir.to_path_buf();
+    set_function(
+      path,
+      "Divina.path",
+      "relative",
+      ctx.create_function(move |_, (path, base): (String, String)| {
+        relative(&directory, &path, &base)
+      }),
+    );
+  });
+}
+
+/// `Divina.glob`, which returns the paths matching a pattern
+fn register_glob<'lua>(ctx: Context<'lua>, divina_table: &Table<'lua>, package_dir: &Path) {
+  let directory = package_dir.to_path_buf();
+  set_function(
+    divina_table,
+    "Divina",
+    "glob",
+    ctx.create_function(move |_, pattern: String| {
+      let paths = glob::glob(&directory.join(&pattern).to_string_lossy()).or_else(|error| {
+        raise(format!(
+          "`Divina.glob` could not parse pattern '{}': {}",
+          pattern, error
+        ))
+      })?;
+
+      // Matches are returned as they were written, relative to the package
+      // if the pattern is
+      Ok(
+        paths
+          .flatten()
+          .map(|path| {
+            path
+              .strip_prefix(&directory)
+              .unwrap_or(&path)
+              .to_string_lossy()
+              .to_string()
+          })
+          .collect::<Vec<_>>(),
+      )
+    }),
+  );
+}
+
+/// `Divina.semver.compare` and `Divina.semver.satisfies`
+fn register_semver<'lua>(ctx: Context<'lua>, divina_table: &Table<'lua>) {
+  set_table(ctx, divina_table, "semver", |semver| {
+    set_function(
+      semver,
+      "Divina.semver",
+      "compare",
+      ctx.create_function(|_, (left, right): (String, String)| compare(&left, &right)),
+    );
+    set_function(
+      semver,
+      "Divina.semver",
+      "satisfies",
+      ctx.create_function(|_, (version, requirement): (String, String)| {
+        satisfies(&version, &requirement)
+      }),
+    );
+  });
+}
+
+/// `Divina.exec`, which runs a command with the system shell and captures its
+/// output
+fn register_exec<'lua>(ctx: Context<'lua>, divina_table: &Table<'lua>, package_dir: &Path) {
+  let directory = package_dir.to_path_buf();
+  set_function(
+    divina_table,
+    "Divina",
+    "exec",
+    ctx.create_function(move |ctx, command: String| {
+      let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+
+        shell.args(["/C", &command]);
+        shell
+      } else {
+        let mut shell = Command::new("sh");
+
+        shell.args(["-c", &command]);
+        shell
+      };
+      let output = shell.current_dir(&directory).output().or_else(|error| {
+        raise(format!(
+          "`Divina.exec` could not run `{}`: {}",
+          command, error
+        ))
+      })?;
+      let result = ctx.create_table()?;
+
+      result.set(
+        "stdout",
+        String::from_utf8_lossy(&output.stdout).to_string(),
+      )?;
+      result.set(
+        "stderr",
+        String::from_utf8_lossy(&output.stderr).to_string(),
+      )?;
+      // `nil` if the command was killed by a signal
+      result.set("exit_code", output.status.code())?;
+
+      Ok(result)
+    }),
+  );
+}
+
+/// Register `Divina.fs`, `Divina.path`, `Divina.glob`, `Divina.semver` and
+/// `Divina.exec` on `divina_table`, resolving relative paths against
+/// `package_dir`
+pub fn register<'lua>(ctx: Context<'lua>, divina_table: &Table<'lua>, package_dir: &Path) {
+  register_fs(ctx, divina_table, package_dir);
+  register_path(ctx, divina_table, package_dir);
+  register_glob(ctx, divina_table, package_dir);
+  register_semver(ctx, divina_table);
+  register_exec(ctx, divina_table, package_dir);
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  #[test]
+  fn compare_orders_versions() {
+    assert_eq!(super::compare("0.1.0", "0.2.0").unwrap(), -1);
+    assert_eq!(super::compare("1.0.0", "1.0.0").unwrap(), 0);
+    assert_eq!(super::compare("1.10.0", "1.9.0").unwrap(), 1);
+    // Pre-releases precede their release
+    assert_eq!(super::compare("1.0.0-alpha", "1.0.0").unwrap(), -1);
+  }
+
+  #[test]
+  fn compare_rejects_invalid_versions() {
+    assert!(super::compare("1.0", "1.0.0").is_err());
+    assert!(super::compare("1.0.0", "latest").is_err());
+  }
+
+  #[test]
+  fn satisfies_matches_requirements() {
+    assert!(super::satisfies("0.1.5", ">=0.1.0, <0.2.0").unwrap());
+    assert!(!super::satisfies("0.2.0", ">=0.1.0, <0.2.0").unwrap());
+    assert!(super::satisfies("1.4.2", "^1.2").unwrap());
+    assert!(!super::satisfies("2.0.0", "^1.2").unwrap());
+  }
+
+  #[test]
+  fn satisfies_rejects_invalid_requirements() {
+    assert!(super::satisfies("1.0.0", "about one").is_err());
+    assert!(super::satisfies("one", "^1.0.0").is_err());
+  }
+
+  #[test]
+  fn relative_walks_between_paths() {
+    let directory = Path::new("/workspace/package");
+
+    assert_eq!(
+      super::relative(directory, "src/x/y.asm", "src").unwrap(),
+      Path::new("x").join("y.asm").to_string_lossy()
+    );
+    assert_eq!(
+      super::relative(directory, "include", "src/x").unwrap(),
+      Path::new("..").join("..").join("include").to_string_lossy()
+    );
+    assert_eq!(
+      super::relative(directory, "/workspace/other", ".").unwrap(),
+      Path::new("..").join("other").to_string_lossy()
+    );
+  }
+}
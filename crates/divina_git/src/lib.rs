@@ -31,6 +31,10 @@ struct State {
 }
 
 fn print(state: &mut State) {
+  if divina_util::output::level() == divina_util::output::Level::Quiet {
+    return;
+  }
+
   let git_stats = state.progress.as_ref().unwrap();
   let network_pct = (100 * git_stats.received_objects()) / git_stats.total_objects();
   let index_pct = (100 * git_stats.indexed_objects()) / git_stats.total_objects();
@@ -76,6 +80,8 @@ fn print(state: &mut State) {
 /// # Errors
 /// never
 pub fn clone(url: &str, path: &str) -> Result<(), git2::Error> {
+  log::debug!("cloning '{}' into '{}'", url, path);
+
   let state = std::cell::RefCell::new(State {
     progress: None,
     total:    0,
@@ -106,7 +112,8 @@ pub fn clone(url: &str, path: &str) -> Result<(), git2::Error> {
     .fetch_options(fo)
     .with_checkout(co)
     .clone(url, path::Path::new(path))?;
-  println!();
+  divina_util::output::status("");
+  log::debug!("cloned '{}' into '{}'", url, path);
 
   Ok(())
 }
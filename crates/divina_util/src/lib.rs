@@ -16,6 +16,8 @@
   html_favicon_url = "https://emojipedia-us.s3.dualstack.us-west-1.amazonaws.com/thumbs/160/twitter/282/ribbon_1f380.png"
 )]
 
+pub mod output;
+
 /// <https://stackoverflow.com/a/32289869/14452787>
 ///
 /// and some help from <https://dev.to/rogertorres/first-steps-with-rust-declarative-macros-1f8m>
@@ -25,7 +27,7 @@ macro_rules! exit_with {
     std::process::exit($exit_code);
   };
   ($exit_code:expr, $($message:tt)*) => {
-    println!($($message)*);
+    $crate::output::error(&format!($($message)*));
 
     std::process::exit($exit_code);
   };
@@ -0,0 +1,218 @@
+// Copyright (C) 2022-2022 Fuwn <contact@fuwn.me>
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Levels and styles of Divina's own output
+//!
+//! Status lines, those prefixed with `::`, go to standard output and are
+//! silenced by `--quiet`. They go to standard error instead while standard
+//! output is reserved for a machine-readable report. Errors, those prefixed
+//! with `!!`, and warnings always go to standard error, even with `--quiet`.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Normal as u8);
+static COLOR: AtomicU8 = AtomicU8::new(ColorChoice::Auto as u8);
+static STATUS: AtomicU8 = AtomicU8::new(Stream::Stdout as u8);
+
+/// How much Divina prints, selected by `--quiet` and `--verbose`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+  Quiet,
+  Normal,
+  Verbose,
+}
+
+/// Kinds of messages which Divina prints, other than errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+  Status,
+  Detail,
+  Warning,
+}
+impl Kind {
+  /// Least level at which the kind is printed
+  const fn level(self) -> Level {
+    match self {
+      Self::Warning => Level::Quiet,
+      Self::Status => Level::Normal,
+      Self::Detail => Level::Verbose,
+    }
+  }
+}
+
+/// Whether a message of `kind` is printed at `level`
+fn shown(kind: Kind, level: Level) -> bool { level >= kind.level() }
+
+/// Whether Divina colors its output, selected by `--color`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+  /// Color a stream only if it is a terminal and `NO_COLOR` is unset
+  Auto,
+  Always,
+  Never,
+}
+impl ColorChoice {
+  #[must_use]
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "auto" => Some(Self::Auto),
+      "always" => Some(Self::Always),
+      "never" => Some(Self::Never),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+  Stdout,
+  Stderr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+  Bold,
+  Error,
+  Warning,
+  Accent,
+}
+impl Style {
+  const fn code(self) -> &'static str {
+    match self {
+      Self::Bold => "\x1b[1m",
+      Self::Error => "\x1b[1;31m",
+      Self::Warning => "\x1b[1;33m",
+      Self::Accent => "\x1b[1;34m",
+    }
+  }
+
+  /// Wrap `text` in the style's escape codes, if `color` is set
+  #[must_use]
+  pub fn paint(self, text: &str, color: bool) -> String {
+    if color {
+      format!("{}{}\x1b[0m", self.code(), text)
+    } else {
+      text.to_string()
+    }
+  }
+}
+
+pub fn set_level(level: Level) { LEVEL.store(level as u8, Ordering::Relaxed); }
+
+#[must_use]
+pub fn level() -> Level {
+  match LEVEL.load(Ordering::Relaxed) {
+    0 => Level::Quiet,
+    2 => Level::Verbose,
+    _ => Level::Normal,
+  }
+}
+
+/// Send status lines and details to `stream`, such as standard error while a
+/// TAP report is printed to standard output
+pub fn set_status_stream(stream: Stream) { STATUS.store(stream as u8, Ordering::Relaxed); }
+
+fn status_stream() -> Stream {
+  match STATUS.load(Ordering::Relaxed) {
+    1 => Stream::Stderr,
+    _ => Stream::Stdout,
+  }
+}
+
+pub fn set_color_choice(choice: ColorChoice) { COLOR.store(choice as u8, Ordering::Relaxed); }
+
+/// Whether output to `stream` is colored
+#[must_use]
+pub fn color(stream: Stream) -> bool {
+  match COLOR.load(Ordering::Relaxed) {
+    1 => true,
+    2 => false,
+    _ =>
+      std::env::var_os("NO_COLOR").map_or(true, |value| value.is_empty())
+        && atty::is(match stream {
+          Stream::Stdout => atty::Stream::Stdout,
+          Stream::Stderr => atty::Stream::Stderr,
+        }),
+  }
+}
+
+/// `Style::paint`, colored if `stream` is
+#[must_use]
+pub fn paint(style: Style, text: &str, stream: Stream) -> String {
+  style.paint(text, color(stream))
+}
+
+/// Style the `::` or `!!` which prefixes `message`, if any
+fn mark(message: &str, stream: Stream) -> String {
+  for (marker, style) in [("::", Style::Accent), ("!!", Style::Error)] {
+    if let Some(rest) = message.strip_prefix(marker) {
+      return format!("{}{}", paint(style, marker, stream), rest);
+    }
+  }
+
+  message.to_string()
+}
+
+/// Print `message` to standard output, whatever the level
+pub fn print(message: &str) {
+  println!("{}", mark(message, Stream::Stdout));
+}
+
+/// Print `message` to the stream which status lines go to
+fn print_status(message: &str) {
+  match status_stream() {
+    Stream::Stdout => print(message),
+    Stream::Stderr => eprintln!("{}", mark(message, Stream::Stderr)),
+  }
+}
+
+/// Print a status line to standard output, unless `--quiet` is passed
+pub fn status(message: &str) {
+  if shown(Kind::Status, level()) {
+    print_status(message);
+  }
+}
+
+/// Print a detail to standard output, only if `--verbose` is passed
+pub fn verbose(message: &str) {
+  if shown(Kind::Detail, level()) {
+    print_status(message);
+  }
+}
+
+/// Print a warning to standard error, whatever the level
+pub fn warning(message: &str) {
+  if shown(Kind::Warning, level()) {
+    eprintln!("{}", mark(message, Stream::Stderr));
+  }
+}
+
+/// Print an error to standard error, whatever the level
+pub fn error(message: &str) {
+  eprintln!("{}", mark(message, Stream::Stderr));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{shown, Kind, Level};
+
+  #[test]
+  fn quiet_only_prints_warnings() {
+    assert!(shown(Kind::Warning, Level::Quiet));
+    assert!(!shown(Kind::Status, Level::Quiet));
+    assert!(!shown(Kind::Detail, Level::Quiet));
+  }
+
+  #[test]
+  fn verbose_prints_everything() {
+    assert!(shown(Kind::Warning, Level::Verbose));
+    assert!(shown(Kind::Status, Level::Verbose));
+    assert!(shown(Kind::Detail, Level::Verbose));
+  }
+
+  #[test]
+  fn details_require_verbose() {
+    assert!(shown(Kind::Status, Level::Normal));
+    assert!(!shown(Kind::Detail, Level::Normal));
+  }
+}